@@ -1,8 +1,11 @@
 use bevy_utils::{HashMap, HashSet};
+use rand::thread_rng;
 use serde::{Deserialize, Serialize};
+use wfc3d::bitset_state::DynBitsetState;
 use wfc3d::cube_grid::CubeGrid;
-use wfc3d::hashset_state::HashsetState;
 use wfc3d::set_rule::*;
+use wfc3d::tile_set::TileSet;
+use wfc3d::Solver;
 
 const RIGHT: (isize, isize, isize) = (1, 0, 0);
 const FRONT: (isize, isize, isize) = (0, 0, -1);
@@ -475,91 +478,67 @@ fn main() {
 
     let prototypes: Prototypes = serde_json::from_str(data).unwrap();
 
-    let mut all_state = HashsetState::<String> {
-        hashset: HashSet::new(),
-    };
-    let mut weights: HashMap<String, u32> = HashMap::new();
-    for prototype in prototypes.0.iter() {
-        all_state.hashset.insert(prototype.0.clone());
-        weights.insert(prototype.0.clone(), prototype.1.weight);
-    }
+    // Intern prototype names so cells only carry compact tile indices. Names
+    // are sorted so each prototype gets the same index on every run.
+    let mut names = prototypes.0.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    let mut tiles = names.iter().cloned().collect::<TileSet<String>>();
+    let all_state = tiles.all_state::<DynBitsetState>();
+    let weights = tiles.weights(|name| prototypes.0[name].weight);
 
-    let observer = WeightedSetCollapseObserver::<String> { weights };
+    let observer = WeightedSetCollapseObserver::<usize> { weights };
     let mut rule = SetCollapseRuleBuilder::new(observer, all_state.clone());
-    for (k, v) in prototypes.0 {
-        rule = rule
-            .allow(
-                &HashsetState::<String>::new_final(&k),
-                &[(
-                    RIGHT,
-                    HashsetState::<String> {
-                        hashset: v.valid_neighbors[0].clone(),
-                    },
-                )],
-            )
-            .allow(
-                &HashsetState::<String>::new_final(&k),
-                &[(
-                    FRONT,
-                    HashsetState::<String> {
-                        hashset: v.valid_neighbors[1].clone(),
-                    },
-                )],
-            )
-            .allow(
-                &HashsetState::<String>::new_final(&k),
-                &[(
-                    LEFT,
-                    HashsetState::<String> {
-                        hashset: v.valid_neighbors[2].clone(),
-                    },
-                )],
-            )
-            .allow(
-                &HashsetState::<String>::new_final(&k),
-                &[(
-                    BACK,
-                    HashsetState::<String> {
-                        hashset: v.valid_neighbors[3].clone(),
-                    },
-                )],
-            )
-            .allow(
-                &HashsetState::<String>::new_final(&k),
-                &[(
-                    ABOVE,
-                    HashsetState::<String> {
-                        hashset: v.valid_neighbors[4].clone(),
-                    },
-                )],
-            )
-            .allow(
-                &HashsetState::<String>::new_final(&k),
-                &[(
-                    BELOW,
-                    HashsetState::<String> {
-                        hashset: v.valid_neighbors[5].clone(),
-                    },
-                )],
-            );
+    for name in &names {
+        let state = tiles.final_state::<DynBitsetState>(name).unwrap();
+        let directions = [RIGHT, FRONT, LEFT, BACK, ABOVE, BELOW];
+        let valid_neighbors = &prototypes.0[name].valid_neighbors;
+        for (direction, valid_neighbors) in directions.into_iter().zip(valid_neighbors) {
+            // Names which aren't prototypes are interned too, after all_state
+            // was taken, so that validation reports them instead of panicking
+            let mut valid_neighbors = valid_neighbors.iter().cloned().collect::<Vec<_>>();
+            valid_neighbors.sort();
+            let allowed = valid_neighbors
+                .into_iter()
+                .map(|name| tiles.insert(name))
+                .collect::<DynBitsetState>();
+            rule = rule.allow(&state, &[(direction, allowed)]);
+        }
+    }
+
+    // Hand written neighbor lists are easy to get wrong, so report anything
+    // suspicious in them before building the rule
+    for diagnostic in rule.validate() {
+        let diagnostic = diagnostic.map_states(|state| tiles.resolve(&state).unwrap());
+        println!("Rule warning: {}", diagnostic);
     }
 
     let cube_dim = 100;
 
-    // TODO: set predetermined states by modifying init_fn based on current coords
     let init_fn = |_x, _y, _z| all_state.clone();
     let mut space = CubeGrid::new(cube_dim, cube_dim, 5, init_fn);
-    wfc3d::collapse(&mut space, &rule.build());
+    let mut solver = Solver::new(rule.build());
+
+    // Pin the center of the bottom layer to a fixed tile before collapsing
+    let center = (cube_dim / 2, 0, cube_dim / 2);
+    let pinned = tiles.final_state(&"p4".to_string()).unwrap();
+    if let Err(err) = solver.pin(&mut space, center, &pinned) {
+        println!("Failed to pin: {}", err);
+        return;
+    }
+    if let Err(err) = solver.collapse(&mut space, &mut thread_rng()) {
+        println!("Failed to collapse: {}", err);
+        return;
+    }
 
     // Print out the collapsed 3x3 cube layer by layer
     for y in 0..5 {
-        print!("Layer: {}\n", y);
+        println!("Layer: {}", y);
         for z in 0..cube_dim {
             for x in 0..cube_dim {
-                print!("{:?} ", (&space[(x, y, z)]));
+                print!("{} ", tiles.resolve(&space[(x, y, z)]).unwrap());
             }
-            print!("\n");
+            println!();
         }
-        print!("\n");
+        println!();
     }
 }
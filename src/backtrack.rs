@@ -0,0 +1,105 @@
+use rand::RngCore;
+use std::collections::VecDeque;
+
+use crate::{CollapseError, CollapseRule, SetState, Solver, Space, State};
+
+// A single observation, along with every change needed to undo it
+struct Decision<C, S> {
+    coordinate: C,
+    observed: S,
+    changes: Vec<(C, S)>,
+}
+
+/// Perform the wave function collapse algorithm like [crate::collapse], but
+/// recover from contradictions by backtracking.
+///
+/// Before each observation the solver starts journaling every cell it
+/// changes. When a contradiction is found, the most recent observation is
+/// undone and the observed state is removed from that cell's possibilities
+/// before solving continues. If that leaves the cell contradictory as well,
+/// the observation before it is undone, and so on.
+///
+/// * `max_depth` - The number of most recent observations which can be
+///   undone. Older observations are committed and their journals dropped, so
+///   this bounds the memory used by the journal. A depth of zero behaves like
+///   [crate::collapse]. Since the search is chronological, large depths can
+///   take a very long time on rule sets with many dead ends.
+///
+/// Returns [CollapseError::Contradiction] if a contradiction is found with no
+/// observations left to undo.
+pub fn collapse_with_backtracking<
+    Rule: CollapseRule<St, Sp>,
+    St: State + SetState,
+    Sp: Space<St>,
+    R: RngCore,
+>(
+    space: &mut Sp,
+    rule: &Rule,
+    max_depth: usize,
+    rng: &mut R,
+) -> Result<(), CollapseError<Sp::Coordinate>> {
+    Solver::new(rule).collapse_with_backtracking(space, max_depth, rng)
+}
+
+impl<St: State + SetState, Sp: Space<St>, Rule: CollapseRule<St, Sp>> Solver<St, Sp, Rule> {
+    /// Perform the wave function collapse algorithm on `space`, backtracking
+    /// on contradictions as with [crate::collapse_with_backtracking].
+    pub fn collapse_with_backtracking<R: RngCore>(
+        &mut self,
+        space: &mut Sp,
+        max_depth: usize,
+        rng: &mut R,
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        let mut decisions = VecDeque::<Decision<Sp::Coordinate, St>>::new();
+        self.restart();
+        self.propagate_initial(space, None, rng)?;
+
+        while let Some(to_collapse) = self.queue.pop(space) {
+            let previous = self.observe(space, to_collapse, rng);
+            let mut changes = vec![(to_collapse, previous)];
+            let observed = space[to_collapse].clone();
+            let mut result = if space[to_collapse].is_contradiction() {
+                Err(to_collapse)
+            } else {
+                self.propagate_observed(space, Some(&mut changes))
+            };
+            decisions.push_back(Decision {
+                coordinate: to_collapse,
+                observed,
+                changes,
+            });
+            if decisions.len() > max_depth {
+                decisions.pop_front();
+            }
+
+            while let Err(coordinate) = result {
+                let Some(decision) = decisions.pop_back() else {
+                    return Err(CollapseError::Contradiction {
+                        coordinate,
+                        last_observed: Some(to_collapse),
+                    });
+                };
+                for (coord, state) in decision.changes.into_iter().rev() {
+                    self.replace_cell(space, coord, state);
+                }
+
+                // Ban the undone observation. The ban belongs to the previous
+                // decision, since it only holds as long as that decision does.
+                let banned = decision.coordinate;
+                let mut state = space[banned].clone();
+                state.clear_states(&decision.observed);
+                let previous = self.replace_cell(space, banned, state);
+                let mut journal = decisions.back_mut().map(|previous| &mut previous.changes);
+                if let Some(journal) = journal.as_deref_mut() {
+                    journal.push((banned, previous));
+                }
+                result = if space[banned].is_contradiction() {
+                    Err(banned)
+                } else {
+                    self.propagate_from(space, banned, journal)
+                };
+            }
+        }
+        Ok(())
+    }
+}
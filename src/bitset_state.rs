@@ -0,0 +1,446 @@
+use crate::{Final, SetState, State};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A state type which represents up to `64 * WORDS` possible states as a
+/// fixed size set of bits, where each state is identified by its bit index.
+///
+/// Set operations work a whole word at a time, which makes this much faster
+/// than [crate::hashset_state::HashsetState] for rule sets with a known upper
+/// bound on the number of states.
+///
+/// * `WORDS` - The number of 64 bit words backing the set
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct BitsetState<const WORDS: usize> {
+    pub words: [u64; WORDS],
+}
+
+impl<const WORDS: usize> BitsetState<WORDS> {
+    /// The number of states this set can hold
+    pub const CAPACITY: usize = WORDS * WORD_BITS;
+
+    /// Creates a new BitsetState without any states inside
+    pub fn empty() -> Self {
+        Self { words: [0; WORDS] }
+    }
+
+    /// Creates a new BitsetState with just the final state `state` inside
+    pub fn new_final(state: usize) -> Self {
+        let mut set = Self::empty();
+        set.insert(state);
+        set
+    }
+
+    /// Creates a new BitsetState which has each state in `states` inside
+    pub fn new(states: &[usize]) -> Self {
+        let mut set = Self::empty();
+        for state in states {
+            set.insert(*state);
+        }
+        set
+    }
+
+    /// Creates a new BitsetState with every state below `count` inside
+    pub fn new_all(count: usize) -> Self {
+        let mut set = Self::empty();
+        fill_words(&mut set.words, count);
+        set
+    }
+
+    /// Adds `state` to the set. Panics if `state` is not below [Self::CAPACITY]
+    pub fn insert(&mut self, state: usize) {
+        self.words[state / WORD_BITS] |= 1 << (state % WORD_BITS);
+    }
+
+    /// Removes `state` from the set
+    pub fn remove(&mut self, state: usize) {
+        if let Some(word) = self.words.get_mut(state / WORD_BITS) {
+            *word &= !(1 << (state % WORD_BITS));
+        }
+    }
+
+    pub fn contains(&self, state: usize) -> bool {
+        contains(&self.words, state)
+    }
+
+    /// The number of states in the set
+    pub fn len(&self) -> usize {
+        count(&self.words)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Iterates over the states in the set in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        iter(&self.words)
+    }
+}
+
+impl<const WORDS: usize> Default for BitsetState<WORDS> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<const WORDS: usize> FromIterator<usize> for BitsetState<WORDS> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::empty();
+        for state in iter {
+            set.insert(state);
+        }
+        set
+    }
+}
+
+impl<const WORDS: usize> State for BitsetState<WORDS> {
+    fn entropy(&self) -> u32 {
+        (self.len() as u32).saturating_sub(1)
+    }
+
+    fn is_contradiction(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl<const WORDS: usize> SetState for BitsetState<WORDS> {
+    fn set_states(&mut self, states: &Self) {
+        for (word, other) in self.words.iter_mut().zip(&states.words) {
+            *word |= other;
+        }
+    }
+
+    fn has_any_of(&self, states: &Self) -> bool {
+        has_any_of(&self.words, &states.words)
+    }
+
+    fn clear_states(&mut self, states: &Self) {
+        for (word, other) in self.words.iter_mut().zip(&states.words) {
+            *word &= !other;
+        }
+    }
+
+    fn collect_final_states(&self, states: &mut Vec<Self>) {
+        states.extend(self.iter().map(Self::new_final));
+    }
+
+    fn for_each_index(&self, f: impl FnMut(usize)) -> bool {
+        self.iter().for_each(f);
+        true
+    }
+}
+
+impl<const WORDS: usize> Final<usize> for BitsetState<WORDS> {
+    fn get(&self) -> Option<usize> {
+        single(&self.words)
+    }
+}
+
+/// A state type like [BitsetState], but with a number of words chosen at
+/// run-time, for when the number of states is only known once rules are
+/// loaded.
+///
+/// Sets of different widths can be combined freely. States past the end of
+/// the backing words are treated as absent.
+#[derive(Default, Debug)]
+pub struct DynBitsetState {
+    pub words: Box<[u64]>,
+}
+
+impl DynBitsetState {
+    /// Creates a new DynBitsetState without any states inside
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new DynBitsetState with just the final state `state` inside
+    pub fn new_final(state: usize) -> Self {
+        let mut set = Self::empty();
+        set.insert(state);
+        set
+    }
+
+    /// Creates a new DynBitsetState which has each state in `states` inside
+    pub fn new(states: &[usize]) -> Self {
+        let mut set = Self::empty();
+        for state in states {
+            set.insert(*state);
+        }
+        set
+    }
+
+    /// Creates a new DynBitsetState with every state below `count` inside
+    pub fn new_all(count: usize) -> Self {
+        let mut words = vec![0; count.div_ceil(WORD_BITS)].into_boxed_slice();
+        fill_words(&mut words, count);
+        Self { words }
+    }
+
+    /// Adds `state` to the set, growing it if needed
+    pub fn insert(&mut self, state: usize) {
+        let index = state / WORD_BITS;
+        if index >= self.words.len() {
+            self.grow(index + 1);
+        }
+        self.words[index] |= 1 << (state % WORD_BITS);
+    }
+
+    /// Removes `state` from the set
+    pub fn remove(&mut self, state: usize) {
+        if let Some(word) = self.words.get_mut(state / WORD_BITS) {
+            *word &= !(1 << (state % WORD_BITS));
+        }
+    }
+
+    pub fn contains(&self, state: usize) -> bool {
+        contains(&self.words, state)
+    }
+
+    /// The number of states in the set
+    pub fn len(&self) -> usize {
+        count(&self.words)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Iterates over the states in the set in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        iter(&self.words)
+    }
+
+    fn grow(&mut self, len: usize) {
+        let mut words = std::mem::take(&mut self.words).into_vec();
+        words.resize(len, 0);
+        self.words = words.into_boxed_slice();
+    }
+}
+
+// Cloning into an existing set reuses its words when the widths match
+impl Clone for DynBitsetState {
+    fn clone(&self) -> Self {
+        Self {
+            words: self.words.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        if self.words.len() == source.words.len() {
+            self.words.copy_from_slice(&source.words);
+        } else {
+            self.words = source.words.clone();
+        }
+    }
+}
+
+// Sets are equal when they hold the same states, regardless of width
+impl PartialEq for DynBitsetState {
+    fn eq(&self, other: &Self) -> bool {
+        let (short, long) = if self.words.len() <= other.words.len() {
+            (&self.words, &other.words)
+        } else {
+            (&other.words, &self.words)
+        };
+        short[..] == long[..short.len()] && long[short.len()..].iter().all(|word| *word == 0)
+    }
+}
+
+impl Eq for DynBitsetState {}
+
+impl FromIterator<usize> for DynBitsetState {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::empty();
+        for state in iter {
+            set.insert(state);
+        }
+        set
+    }
+}
+
+impl State for DynBitsetState {
+    fn entropy(&self) -> u32 {
+        (self.len() as u32).saturating_sub(1)
+    }
+
+    fn is_contradiction(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl SetState for DynBitsetState {
+    fn set_states(&mut self, states: &Self) {
+        if states.words.len() > self.words.len() {
+            self.grow(states.words.len());
+        }
+        for (word, other) in self.words.iter_mut().zip(&states.words[..]) {
+            *word |= other;
+        }
+    }
+
+    fn has_any_of(&self, states: &Self) -> bool {
+        has_any_of(&self.words, &states.words)
+    }
+
+    fn clear_states(&mut self, states: &Self) {
+        for (word, other) in self.words.iter_mut().zip(&states.words[..]) {
+            *word &= !other;
+        }
+    }
+
+    fn collect_final_states(&self, states: &mut Vec<Self>) {
+        states.extend(self.iter().map(Self::new_final));
+    }
+
+    fn for_each_index(&self, f: impl FnMut(usize)) -> bool {
+        self.iter().for_each(f);
+        true
+    }
+}
+
+impl Final<usize> for DynBitsetState {
+    fn get(&self) -> Option<usize> {
+        single(&self.words)
+    }
+}
+
+// Sets the lowest `count` bits of `words`
+fn fill_words(words: &mut [u64], count: usize) {
+    for (i, word) in words.iter_mut().enumerate() {
+        let remaining = count.saturating_sub(i * WORD_BITS);
+        *word = if remaining >= WORD_BITS {
+            u64::MAX
+        } else {
+            (1 << remaining) - 1
+        };
+    }
+}
+
+fn contains(words: &[u64], state: usize) -> bool {
+    words
+        .get(state / WORD_BITS)
+        .is_some_and(|word| word & (1 << (state % WORD_BITS)) != 0)
+}
+
+fn count(words: &[u64]) -> usize {
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+fn has_any_of(words: &[u64], states: &[u64]) -> bool {
+    words
+        .iter()
+        .zip(states)
+        .any(|(word, other)| word & other != 0)
+}
+
+fn iter(words: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    words.iter().enumerate().flat_map(|(i, word)| {
+        let mut word = *word;
+        std::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let bit = word.trailing_zeros() as usize;
+            word &= word - 1;
+            Some(i * WORD_BITS + bit)
+        })
+    })
+}
+
+// Gets the only state in `words`, if there is exactly one
+fn single(words: &[u64]) -> Option<usize> {
+    let mut found = None;
+    for (i, word) in words.iter().enumerate() {
+        if *word != 0 {
+            if found.is_some() || word.count_ones() != 1 {
+                return None;
+            }
+            found = Some(i * WORD_BITS + word.trailing_zeros() as usize);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_words_at_word_boundaries() {
+        let mut words = [0; 2];
+        fill_words(&mut words, 63);
+        assert_eq!(words, [u64::MAX >> 1, 0]);
+        fill_words(&mut words, 64);
+        assert_eq!(words, [u64::MAX, 0]);
+        fill_words(&mut words, 65);
+        assert_eq!(words, [u64::MAX, 1]);
+        fill_words(&mut words, 128);
+        assert_eq!(words, [u64::MAX, u64::MAX]);
+        fill_words(&mut words, 0);
+        assert_eq!(words, [0, 0]);
+    }
+
+    #[test]
+    fn single_at_word_boundaries() {
+        for state in [0, 63, 64, 127] {
+            assert_eq!(
+                single(&BitsetState::<2>::new_final(state).words),
+                Some(state)
+            );
+        }
+        assert_eq!(single(&BitsetState::<2>::new(&[63, 64]).words), None);
+        assert_eq!(single(&BitsetState::<2>::new(&[0, 1]).words), None);
+        assert_eq!(single(&[0, 0]), None);
+    }
+
+    #[test]
+    fn iter_at_word_boundaries() {
+        let states = [0, 63, 64, 127, 128];
+        let set = BitsetState::<3>::new(&states);
+        assert_eq!(iter(&set.words).collect::<Vec<_>>(), states);
+        assert_eq!(set.len(), states.len());
+
+        let all = DynBitsetState::new_all(127);
+        assert_eq!(all.words.len(), 2);
+        assert!(all.iter().eq(0..127));
+        assert!(all.contains(126) && !all.contains(127));
+    }
+
+    #[test]
+    fn dyn_sets_of_different_widths() {
+        let narrow = DynBitsetState::new(&[1, 63]);
+        let wide = DynBitsetState::new(&[63, 64, 127]);
+        assert_eq!((narrow.words.len(), wide.words.len()), (1, 2));
+
+        let mut union = narrow.clone();
+        union.set_states(&wide);
+        assert!(union.iter().eq([1, 63, 64, 127]));
+
+        let mut cleared = wide.clone();
+        cleared.clear_states(&narrow);
+        assert!(cleared.iter().eq([64, 127]));
+        let mut cleared = narrow.clone();
+        cleared.clear_states(&wide);
+        assert!(cleared.iter().eq([1]));
+
+        assert!(narrow.has_any_of(&wide) && wide.has_any_of(&narrow));
+        assert!(!DynBitsetState::new(&[1]).has_any_of(&DynBitsetState::new(&[127])));
+    }
+
+    #[test]
+    fn dyn_equality_ignores_width() {
+        let narrow = DynBitsetState::new(&[5]);
+        let mut wide = DynBitsetState::new(&[5, 127]);
+        assert_ne!(narrow, wide);
+        wide.remove(127);
+        assert_eq!(wide.words.len(), 2);
+        assert_eq!(narrow, wide);
+        assert_eq!(wide, narrow);
+        assert_eq!(DynBitsetState::empty(), DynBitsetState::new_all(0));
+
+        let mut cleared = wide.clone();
+        cleared.remove(5);
+        assert_eq!(cleared, DynBitsetState::empty());
+    }
+}
@@ -1,4 +1,54 @@
 use crate::{Space, State};
+use rand::RngCore;
+use std::marker::PhantomData;
+
+/// Borrowed view of the states of a cell's neighbors, in the order given by
+/// [CollapseRule::neighbor_offsets].
+///
+/// States are read straight out of the space, so no neighbor state is copied
+/// when a rule inspects it.
+pub struct Neighbors<'a, S, Sp: Space<S>> {
+    space: &'a Sp,
+    coordinates: &'a [Option<Sp::Coordinate>],
+    _state: PhantomData<fn() -> S>,
+}
+
+impl<'a, S: 'a, Sp: Space<S>> Neighbors<'a, S, Sp> {
+    /// Creates a view of the cells at `coordinates` in `space`, where `None`
+    /// marks a neighbor which is out of bounds for the space.
+    pub fn new(space: &'a Sp, coordinates: &'a [Option<Sp::Coordinate>]) -> Self {
+        Self {
+            space,
+            coordinates,
+            _state: PhantomData,
+        }
+    }
+
+    /// The number of neighbors, including ones which don't exist
+    pub fn len(&self) -> usize {
+        self.coordinates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coordinates.is_empty()
+    }
+
+    /// Gets the state of the `index`th neighbor, or `None` if it doesn't exist
+    pub fn get(&self, index: usize) -> Option<&'a S> {
+        self.coordinates[index].map(|coord| &self.space[coord])
+    }
+
+    /// Gets the coordinate of the `index`th neighbor, or `None` if it doesn't
+    /// exist
+    pub fn coordinate(&self, index: usize) -> Option<Sp::Coordinate> {
+        self.coordinates[index]
+    }
+
+    /// Iterates over the states of every neighbor as with [Neighbors::get]
+    pub fn iter(&self) -> impl Iterator<Item = Option<&'a S>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
 
 /// Collapse rules define the relationships between a cell's possible state
 /// based on it's neighbors.
@@ -16,12 +66,51 @@ pub trait CollapseRule<S: State, Sp: 'static + Space<S>> {
     ///
     /// * `cell` - The cell state to modify
     /// * `neighbors` - The states of neighbors in the order specified by
-    /// `NEIGHBOR_DIRECTIONS`. `Some(<state>)` if the cell exists, and `None`
-    /// otherwise.
-    fn collapse(&self, cell: &mut S, neighbors: &[Option<S>]);
+    ///   `neighbor_offsets()`. `Some(<state>)` if the cell exists, and `None`
+    ///   otherwise.
+    fn collapse(&self, cell: &mut S, neighbors: &Neighbors<S, Sp>);
     /// The observe rule, which forces a cell into a zero-entropy state.
     ///
     /// * `cell` - The cell to observe
     /// * `neighbors` - The states of neighbor cells as in `collapse()` above.
-    fn observe(&self, cell: &mut S, neighbors: &[Option<S>]);
+    /// * `rng` - Source of randomness for choosing between possible states.
+    ///   Observations should draw from this rather than any other source so
+    ///   that seeded runs are reproducible.
+    fn observe(&self, cell: &mut S, neighbors: &Neighbors<S, Sp>, rng: &mut dyn RngCore);
+    /// Checks `cell` against its neighbors, as used by [crate::verify].
+    ///
+    /// * `cell` - The cell to check
+    /// * `neighbors` - The states of neighbor cells as in `collapse()` above.
+    /// * `conflicts` - Output list of the indices of neighbors which `cell`
+    ///   isn't allowed next to. Out of bounds neighbors can conflict as well.
+    ///
+    /// By default, every neighbor is reported as conflicting if `collapse()`
+    /// would remove any state from the cell, since there's no way to tell
+    /// which neighbor is at fault. Rules which can tell should override this.
+    fn conflicts(&self, cell: &S, neighbors: &Neighbors<S, Sp>, conflicts: &mut Vec<usize>) {
+        let mut collapsed = cell.clone();
+        self.collapse(&mut collapsed, neighbors);
+        if collapsed != *cell {
+            conflicts.extend(0..neighbors.len());
+        }
+    }
+}
+
+// Rules can be borrowed, so a [crate::Solver] doesn't have to own its rule
+impl<S: State, Sp: Space<S>, Rule: CollapseRule<S, Sp> + ?Sized> CollapseRule<S, Sp> for &Rule {
+    fn neighbor_offsets(&self) -> Box<[Sp::CoordinateDelta]> {
+        (**self).neighbor_offsets()
+    }
+
+    fn collapse(&self, cell: &mut S, neighbors: &Neighbors<S, Sp>) {
+        (**self).collapse(cell, neighbors)
+    }
+
+    fn observe(&self, cell: &mut S, neighbors: &Neighbors<S, Sp>, rng: &mut dyn RngCore) {
+        (**self).observe(cell, neighbors, rng)
+    }
+
+    fn conflicts(&self, cell: &S, neighbors: &Neighbors<S, Sp>, conflicts: &mut Vec<usize>) {
+        (**self).conflicts(cell, neighbors, conflicts)
+    }
 }
@@ -11,12 +11,14 @@ Cube as viewed from above
      (0,0,0) - - - - (width,0,0)
 */
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct CubeGrid<T> {
     cells: Box<[T]>,
     width: isize,
     length: isize,
     height: isize,
+    // Whether neighbors wrap around the x, y and z axes
+    wrap: (bool, bool, bool),
 }
 
 impl InvertDelta for (isize, isize, isize) {
@@ -50,8 +52,32 @@ impl<T> CubeGrid<T> {
             width,
             length,
             height,
+            wrap: (false, false, false),
         }
     }
+
+    // Makes neighbors wrap around the grid on each axis that's set, so cells
+    // on one edge are neighbors of the cells on the opposite edge and the
+    // collapsed grid tiles seamlessly along that axis. Axes which don't wrap
+    // have out of bounds neighbors past their edges, as by default.
+    //
+    // On a wrapping axis of size 1, a cell is its own neighbor.
+    pub fn with_wrap(mut self, wrap_x: bool, wrap_y: bool, wrap_z: bool) -> Self {
+        self.wrap = (wrap_x, wrap_y, wrap_z);
+        self
+    }
+}
+
+// Wraps `value` into `0..size` if `wrap` is set, otherwise checks that it's
+// already in bounds
+pub(crate) fn wrap_axis(value: isize, size: isize, wrap: bool) -> Option<isize> {
+    if wrap {
+        Some(value.rem_euclid(size))
+    } else if (0..size).contains(&value) {
+        Some(value)
+    } else {
+        None
+    }
 }
 
 // Access to a certain cells possible states
@@ -103,18 +129,21 @@ impl<T: 'static> Space<T> for CubeGrid<T> {
     ) {
         assert!(neighbor_directions.len() <= neighbors.len());
 
+        // Neighbors past the edge of the grid are None, so that boundary
+        // constraints of rules apply to the cells on that edge, unless the axis
+        // wraps around
         let (x, y, z) = coord;
+        let (wrap_x, wrap_y, wrap_z) = self.wrap;
         for i in 0..neighbor_directions.len() {
             let (dx, dy, dz) = neighbor_directions[i];
-            let (nx, ny, nz) = (x + dx, y + dy, z + dz);
-            if nx.clamp(0, self.width - 1) == nx
-                && nz.clamp(0, self.length - 1) == nz
-                && ny.clamp(0, self.height - 1) == ny
-            {
-                neighbors[i] = Some((nx, ny, nz));
-            } else {
-                neighbors[i] = None;
-            }
+            neighbors[i] = match (
+                wrap_axis(x + dx, self.width, wrap_x),
+                wrap_axis(y + dy, self.height, wrap_y),
+                wrap_axis(z + dz, self.length, wrap_z),
+            ) {
+                (Some(nx), Some(ny), Some(nz)) => Some((nx, ny, nz)),
+                _ => None,
+            };
         }
     }
 }
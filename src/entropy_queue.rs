@@ -0,0 +1,67 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{Space, State};
+
+// Priority queue of cells to observe, lowest entropy first.
+//
+// Entries are never updated in place. Instead a new entry is pushed whenever a
+// cell's entropy changes, and entries which no longer match the cell's entropy
+// are skipped when popped.
+//
+// Ties between cells of equal entropy are broken at random. Each entry gets a
+// random key when pushed, drawn from a generator seeded from the caller's rng
+// in `reset`, so the same seed still breaks ties the same way.
+pub(crate) struct EntropyQueue<C> {
+    heap: BinaryHeap<Reverse<(u32, u64, C)>>,
+    ties: StdRng,
+}
+
+impl<C: Copy + Ord> EntropyQueue<C> {
+    pub(crate) fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            ties: StdRng::seed_from_u64(0),
+        }
+    }
+
+    // Empties the queue and reseeds the keys used to break ties from `rng`
+    pub(crate) fn reset(&mut self, rng: &mut dyn RngCore) {
+        self.heap.clear();
+        self.ties = StdRng::seed_from_u64(rng.next_u64());
+    }
+
+    pub(crate) fn push(&mut self, coord: C, entropy: u32) {
+        let tie = self.ties.next_u64();
+        self.heap.push(Reverse((entropy, tie, coord)));
+    }
+
+    // Gets the lowest entropy of any unresolved cell, dropping stale entries
+    pub(crate) fn min_entropy<St: State, Sp: Space<St, Coordinate = C>>(
+        &mut self,
+        space: &Sp,
+    ) -> Option<u32> {
+        while let Some(Reverse((entropy, _, coord))) = self.heap.peek() {
+            let current = space[*coord].entropy();
+            if current != 0 && current == *entropy {
+                return Some(current);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    // Pops the unresolved cell with the lowest entropy
+    pub(crate) fn pop<St: State, Sp: Space<St, Coordinate = C>>(
+        &mut self,
+        space: &Sp,
+    ) -> Option<C> {
+        while let Some(Reverse((entropy, _, coord))) = self.heap.pop() {
+            let current = space[coord].entropy();
+            if current != 0 && current == entropy {
+                return Some(coord);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::{self, Debug, Display};
+
+/// Errors which can stop [crate::collapse] from producing a valid result.
+///
+/// * `C` - The coordinate type of the space being collapsed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollapseError<C> {
+    /// A cell was left with no possible states.
+    ///
+    /// * `coordinate` - The cell which has no remaining states
+    /// * `last_observed` - The cell most recently observed before the
+    ///   contradiction was found, or `None` if it was found before any cell
+    ///   was observed
+    Contradiction {
+        coordinate: C,
+        last_observed: Option<C>,
+    },
+    /// A pinned cell couldn't be satisfied along with the states around it,
+    /// such as earlier pins or the initial states of the space.
+    ///
+    /// * `coordinate` - The cell left with no remaining states
+    /// * `pinned` - The cell whose pin caused the conflict
+    PinConflict { coordinate: C, pinned: C },
+}
+
+impl<C: Debug> Display for CollapseError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Contradiction {
+                coordinate,
+                last_observed: Some(last_observed),
+            } => write!(
+                f,
+                "contradiction at {:?} after observing {:?}",
+                coordinate, last_observed
+            ),
+            Self::Contradiction {
+                coordinate,
+                last_observed: None,
+            } => write!(
+                f,
+                "contradiction at {:?} before any observation",
+                coordinate
+            ),
+            Self::PinConflict { coordinate, pinned } => write!(
+                f,
+                "pin at {:?} leaves {:?} without any possible states",
+                pinned, coordinate
+            ),
+        }
+    }
+}
+
+impl<C: Debug> std::error::Error for CollapseError<C> {}
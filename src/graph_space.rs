@@ -0,0 +1,131 @@
+use crate::{InvertDelta, Space};
+use std::ops::{Index, IndexMut};
+
+/// Identifies a node of a [GraphSpace]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeId(pub usize);
+
+/// A space made of arbitrary nodes connected by labeled edges, for adjacency
+/// that doesn't fit a regular lattice, such as irregular meshes or room
+/// graphs.
+///
+/// Edge labels act as the coordinate deltas of the space, so a rule asks for
+/// the neighbor along each label, like ports or directions. Each node has at
+/// most one edge with a given label, and nodes without an edge for a label
+/// have an out of bounds neighbor there.
+///
+/// * `T` - The state of each node
+/// * `L` - The edge label type
+#[derive(Clone, Debug)]
+pub struct GraphSpace<T, L> {
+    cells: Vec<T>,
+    edges: Vec<Vec<(L, NodeId)>>,
+}
+
+impl<T, L: PartialEq> GraphSpace<T, L> {
+    /// Creates a new GraphSpace without any nodes
+    pub fn new() -> Self {
+        Self {
+            cells: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Adds a node with the initial state `state`, returning its id. Ids are
+    /// handed out in insertion order starting from zero.
+    pub fn add_node(&mut self, state: T) -> NodeId {
+        self.cells.push(state);
+        self.edges.push(Vec::new());
+        NodeId(self.cells.len() - 1)
+    }
+
+    /// Adds an edge from `from` to `to` with the label `label`, replacing any
+    /// edge from `from` which already has that label. The edge only goes one
+    /// way, see [GraphSpace::connect] for adding both directions.
+    pub fn add_edge(&mut self, from: NodeId, label: L, to: NodeId) {
+        assert!(to.0 < self.cells.len(), "edge to unknown node");
+        let edges = &mut self.edges[from.0];
+        if let Some(edge) = edges.iter_mut().find(|(other, _)| *other == label) {
+            edge.1 = to;
+        } else {
+            edges.push((label, to));
+        }
+    }
+
+    /// Adds an edge from `a` to `b` with the label `label`, and the edge back
+    /// from `b` to `a` with the inverse label
+    pub fn connect(&mut self, a: NodeId, label: L, b: NodeId)
+    where
+        L: InvertDelta,
+    {
+        let inverse = label.invert_delta();
+        self.add_edge(a, label, b);
+        self.add_edge(b, inverse, a);
+    }
+
+    /// Gets the neighbor of `node` along the edge labeled `label`
+    pub fn neighbor(&self, node: NodeId, label: &L) -> Option<NodeId> {
+        self.edges[node.0]
+            .iter()
+            .find(|(other, _)| other == label)
+            .map(|(_, neighbor)| *neighbor)
+    }
+
+    /// Every edge from `node`, in the order they were added
+    pub fn edges(&self, node: NodeId) -> &[(L, NodeId)] {
+        &self.edges[node.0]
+    }
+
+    /// The number of nodes in the space
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+impl<T, L: PartialEq> Default for GraphSpace<T, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Access to a certain nodes possible states
+impl<T: 'static, L: PartialEq + 'static> Index<NodeId> for GraphSpace<T, L> {
+    type Output = T;
+
+    fn index(&self, index: NodeId) -> &Self::Output {
+        &self.cells[index.0]
+    }
+}
+
+// Mutable access to a certain nodes possible states
+impl<T: 'static, L: PartialEq + 'static> IndexMut<NodeId> for GraphSpace<T, L> {
+    fn index_mut(&mut self, index: NodeId) -> &mut Self::Output {
+        &mut self.cells[index.0]
+    }
+}
+
+impl<T: 'static, L: PartialEq + 'static> Space<T> for GraphSpace<T, L> {
+    type Coordinate = NodeId;
+    type CoordinateDelta = L;
+
+    fn coordinate_list(&self) -> Box<[Self::Coordinate]> {
+        (0..self.cells.len()).map(NodeId).collect()
+    }
+
+    fn neighbors(
+        &self,
+        coord: Self::Coordinate,
+        neighbor_directions: &[Self::CoordinateDelta],
+        neighbors: &mut [Option<Self::Coordinate>],
+    ) {
+        assert!(neighbor_directions.len() <= neighbors.len());
+
+        for (neighbor, label) in neighbors.iter_mut().zip(neighbor_directions) {
+            *neighbor = self.neighbor(coord, label);
+        }
+    }
+}
@@ -31,6 +31,14 @@ impl<T: Eq + Hash + Clone> HashsetState<T> {
     }
 }
 
+impl<T: Eq + Hash> FromIterator<T> for HashsetState<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            hashset: iter.into_iter().collect(),
+        }
+    }
+}
+
 impl<T: Clone + Eq + Hash> State for HashsetState<T> {
     fn entropy(&self) -> u32 {
         if self.hashset.is_empty() {
@@ -38,9 +46,15 @@ impl<T: Clone + Eq + Hash> State for HashsetState<T> {
         }
         self.hashset.len() as u32 - 1
     }
+
+    fn is_contradiction(&self) -> bool {
+        self.hashset.is_empty()
+    }
 }
 
-impl<T: Clone + Eq + Hash> SetState for HashsetState<T> {
+// Requires `T: Ord` so that final states are always collected in the same
+// order, independent of the hash set's iteration order.
+impl<T: Clone + Eq + Hash + Ord> SetState for HashsetState<T> {
     fn has_any_of(&self, states: &Self) -> bool {
         !self.hashset.is_disjoint(&states.hashset)
     }
@@ -56,12 +70,9 @@ impl<T: Clone + Eq + Hash> SetState for HashsetState<T> {
     }
 
     fn collect_final_states(&self, states: &mut Vec<Self>) {
-        self.hashset
-            .iter()
-            .map(|x| {
-                states.push(Self::new_final(x));
-            })
-            .for_each(drop);
+        let mut sorted = self.hashset.iter().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        states.extend(sorted.into_iter().map(Self::new_final));
     }
 }
 
@@ -0,0 +1,256 @@
+use crate::{InvertDelta, Space};
+use std::ops::{Index, IndexMut};
+
+/*
+Uses axial coordinates for pointy-top hexagons. q increases to the east, and r
+increases to the south-east, so each row of hexagons shares the same r.
+
+Grids are laid out as rectangles of rows, with odd rows shifted half a hexagon
+east. The first cell of row r has q = -(r / 2), rounding down.
+
+ (0,0) (1,0) (2,0)
+    (0,1) (1,1) (2,1)
+(-1,2) (0,2) (1,2)
+*/
+
+/// An axial hex coordinate, or the offset between two of them
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default)]
+pub struct Axial {
+    pub q: isize,
+    pub r: isize,
+}
+
+impl Axial {
+    pub const EAST: Self = Self::new(1, 0);
+    pub const NORTH_EAST: Self = Self::new(1, -1);
+    pub const NORTH_WEST: Self = Self::new(0, -1);
+    pub const WEST: Self = Self::new(-1, 0);
+    pub const SOUTH_WEST: Self = Self::new(-1, 1);
+    pub const SOUTH_EAST: Self = Self::new(0, 1);
+
+    /// The offsets to the six neighbors of a hexagon, counter-clockwise from
+    /// east
+    pub const DIRECTIONS: [Self; 6] = [
+        Self::EAST,
+        Self::NORTH_EAST,
+        Self::NORTH_WEST,
+        Self::WEST,
+        Self::SOUTH_WEST,
+        Self::SOUTH_EAST,
+    ];
+
+    pub const fn new(q: isize, r: isize) -> Self {
+        Self { q, r }
+    }
+}
+
+impl std::ops::Add for Axial {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl InvertDelta for Axial {
+    fn invert_delta(&self) -> Self {
+        Self::new(-self.q, -self.r)
+    }
+}
+
+impl InvertDelta for (Axial, isize) {
+    fn invert_delta(&self) -> Self {
+        let (delta, dlayer) = *self;
+        (delta.invert_delta(), -dlayer)
+    }
+}
+
+// The rectangular layout shared by hex grids, mapping coordinates to the
+// index of their cell within a layer
+#[derive(Clone, Copy, Debug)]
+struct HexLayout {
+    width: isize,
+    height: isize,
+}
+
+impl HexLayout {
+    fn len(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    fn index(&self, coord: Axial) -> Option<usize> {
+        let column = coord.q + coord.r.div_euclid(2);
+        if (0..self.height).contains(&coord.r) && (0..self.width).contains(&column) {
+            Some((coord.r * self.width + column) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn coordinates(&self) -> impl Iterator<Item = Axial> + '_ {
+        (0..self.height).flat_map(move |r| {
+            (0..self.width).map(move |column| Axial::new(column - r.div_euclid(2), r))
+        })
+    }
+}
+
+/// A grid of hexagons in axial coordinates, where each cell has six
+/// neighbors in the directions of [Axial::DIRECTIONS]
+#[derive(Clone, Debug)]
+pub struct HexGrid<T> {
+    cells: Box<[T]>,
+    layout: HexLayout,
+}
+
+impl<T> HexGrid<T> {
+    // width - number of hexagons in each row
+    // height - number of rows
+    // init_fn - callback to set the initial state of each cell based on coordinate
+    pub fn new(width: isize, height: isize, init_fn: impl Fn(Axial) -> T) -> Self {
+        let layout = HexLayout { width, height };
+        Self {
+            cells: layout.coordinates().map(init_fn).collect(),
+            layout,
+        }
+    }
+
+    /// Checks if `coord` is inside the grid
+    pub fn contains(&self, coord: Axial) -> bool {
+        self.layout.index(coord).is_some()
+    }
+}
+
+// Access to a certain cells possible states
+impl<T: 'static> Index<Axial> for HexGrid<T> {
+    type Output = T;
+
+    fn index(&self, index: Axial) -> &Self::Output {
+        let index = self.layout.index(index).expect("coordinate outside grid");
+        &self.cells[index]
+    }
+}
+
+// Mutable access to a certain cells possible states
+impl<T: 'static> IndexMut<Axial> for HexGrid<T> {
+    fn index_mut(&mut self, index: Axial) -> &mut Self::Output {
+        let index = self.layout.index(index).expect("coordinate outside grid");
+        &mut self.cells[index]
+    }
+}
+
+impl<T: 'static> Space<T> for HexGrid<T> {
+    type Coordinate = Axial;
+    type CoordinateDelta = Axial;
+
+    fn coordinate_list(&self) -> Box<[Self::Coordinate]> {
+        self.layout.coordinates().collect()
+    }
+
+    fn neighbors(
+        &self,
+        coord: Self::Coordinate,
+        neighbor_directions: &[Self::CoordinateDelta],
+        neighbors: &mut [Option<Self::Coordinate>],
+    ) {
+        assert!(neighbor_directions.len() <= neighbors.len());
+
+        for (neighbor, delta) in neighbors.iter_mut().zip(neighbor_directions) {
+            let neighbor_coord = coord + *delta;
+            *neighbor = self.contains(neighbor_coord).then_some(neighbor_coord);
+        }
+    }
+}
+
+/// Layers of [HexGrid]s stacked on top of each other, where each cell also
+/// has neighbors in the layers above and below it.
+///
+/// Coordinates and deltas are an axial coordinate paired with a layer, where
+/// higher layers are above lower ones. The neighbor directly above is at
+/// `(Axial::default(), 1)`.
+#[derive(Clone, Debug)]
+pub struct HexPrismGrid<T> {
+    cells: Box<[T]>,
+    layout: HexLayout,
+    layers: isize,
+}
+
+impl<T> HexPrismGrid<T> {
+    // width - number of hexagons in each row
+    // height - number of rows
+    // layers - number of layers stacked on top of each other
+    // init_fn - callback to set the initial state of each cell based on coordinate
+    pub fn new(
+        width: isize,
+        height: isize,
+        layers: isize,
+        init_fn: impl Fn(Axial, isize) -> T,
+    ) -> Self {
+        let layout = HexLayout { width, height };
+        let cells = (0..layers)
+            .flat_map(|layer| layout.coordinates().map(move |coord| (coord, layer)))
+            .map(|(coord, layer)| init_fn(coord, layer))
+            .collect();
+        Self {
+            cells,
+            layout,
+            layers,
+        }
+    }
+
+    /// Checks if `coord` is inside the grid
+    pub fn contains(&self, coord: (Axial, isize)) -> bool {
+        self.index_of(coord).is_some()
+    }
+
+    fn index_of(&self, (coord, layer): (Axial, isize)) -> Option<usize> {
+        if !(0..self.layers).contains(&layer) {
+            return None;
+        }
+        let index = self.layout.index(coord)?;
+        Some(layer as usize * self.layout.len() + index)
+    }
+}
+
+// Access to a certain cells possible states
+impl<T: 'static> Index<(Axial, isize)> for HexPrismGrid<T> {
+    type Output = T;
+
+    fn index(&self, index: (Axial, isize)) -> &Self::Output {
+        let index = self.index_of(index).expect("coordinate outside grid");
+        &self.cells[index]
+    }
+}
+
+// Mutable access to a certain cells possible states
+impl<T: 'static> IndexMut<(Axial, isize)> for HexPrismGrid<T> {
+    fn index_mut(&mut self, index: (Axial, isize)) -> &mut Self::Output {
+        let index = self.index_of(index).expect("coordinate outside grid");
+        &mut self.cells[index]
+    }
+}
+
+impl<T: 'static> Space<T> for HexPrismGrid<T> {
+    type Coordinate = (Axial, isize);
+    type CoordinateDelta = (Axial, isize);
+
+    fn coordinate_list(&self) -> Box<[Self::Coordinate]> {
+        (0..self.layers)
+            .flat_map(|layer| self.layout.coordinates().map(move |coord| (coord, layer)))
+            .collect()
+    }
+
+    fn neighbors(
+        &self,
+        coord: Self::Coordinate,
+        neighbor_directions: &[Self::CoordinateDelta],
+        neighbors: &mut [Option<Self::Coordinate>],
+    ) {
+        assert!(neighbor_directions.len() <= neighbors.len());
+
+        let (coord, layer) = coord;
+        for (neighbor, (delta, dlayer)) in neighbors.iter_mut().zip(neighbor_directions) {
+            let neighbor_coord = (coord + *delta, layer + dlayer);
+            *neighbor = self.contains(neighbor_coord).then_some(neighbor_coord);
+        }
+    }
+}
@@ -6,136 +6,103 @@
 //! cells (such as a square grid) from all possible states to only the states
 //! possible with a given ruleset, selecting randomly where ambiguous.
 
+mod backtrack;
+pub mod bitset_state;
 mod collapse_rule;
 pub mod cube_grid;
+mod entropy_queue;
+mod error;
+pub mod graph_space;
 pub mod hashset_state;
+pub mod hex_grid;
+mod pin;
+mod retry;
 pub mod set_rule;
 mod set_state;
+mod solver;
 mod space;
+pub mod sparse_grid;
+pub mod square_grid;
 mod state;
+mod support;
+pub mod tile_set;
+mod verify;
 
-use std::collections::{HashSet, VecDeque};
-
+pub use backtrack::*;
 pub use collapse_rule::*;
-use rand::{thread_rng, Rng};
+use entropy_queue::EntropyQueue;
+pub use error::*;
+pub use pin::*;
+use rand::RngCore;
+pub use retry::*;
 pub use set_state::*;
+pub use solver::*;
 pub use space::*;
 pub use state::*;
-
-fn find_next_to_collapse<St: State, Sp: Space<St>>(
-    unresoved_set: &mut HashSet<Sp::Coordinate>,
-    lowest_entropy_set: &mut Vec<Sp::Coordinate>,
-    resolved_set: &mut HashSet<Sp::Coordinate>,
-    space: &Sp,
-) -> Option<Sp::Coordinate> {
-    let mut lowest_entropy = std::u32::MAX;
-    lowest_entropy_set.clear();
-    resolved_set.clear();
-    for unresolved in unresoved_set.iter() {
-        let entropy = space[*unresolved].entropy();
-        if entropy == 0 {
-            resolved_set.insert(*unresolved);
-        } else if entropy < lowest_entropy {
-            lowest_entropy = entropy;
-            lowest_entropy_set.clear();
-            lowest_entropy_set.push(*unresolved);
-        } else if entropy == lowest_entropy {
-            lowest_entropy_set.push(*unresolved);
-        }
-    }
-    unresoved_set.retain(|x| !resolved_set.contains(x));
-    if lowest_entropy_set.is_empty() {
-        None
-    } else {
-        Some(lowest_entropy_set[thread_rng().gen_range(0..lowest_entropy_set.len())])
-    }
-}
+pub use support::*;
+pub use verify::*;
 
 /// Perform the wave function collapse algorithm on a given state-space with
 /// the provided collapse rule.
-pub fn collapse<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>>(space: &mut Sp, rule: &Rule) {
-    let mut unresolved_set = HashSet::new();
-    let mut resolved_set = HashSet::new();
-    let mut lowest_entropy_set = Vec::new();
-    let neighbor_directions = rule.neighbor_offsets();
-    for coord in &space.coordinate_list()[..] {
-        if space[*coord].entropy() > 0 {
-            unresolved_set.insert(*coord);
-        }
-    }
-    let mut neighbors = vec![None; neighbor_directions.len()].into_boxed_slice();
-    let mut neighbor_states =
-        vec![Option::<St>::None; neighbor_directions.len()].into_boxed_slice();
-    let mut to_propagate = VecDeque::new();
+///
+/// All random choices are drawn from `rng`, so collapsing the same space with
+/// the same rule and an identically seeded rng gives the same result.
+///
+/// Returns [CollapseError::Contradiction] as soon as any cell is left with no
+/// possible states, in which case the space is left partially collapsed.
+///
+/// To collapse many spaces with the same rule, use a [Solver] instead, which
+/// keeps its buffers between runs.
+pub fn collapse<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>, R: RngCore>(
+    space: &mut Sp,
+    rule: &Rule,
+    rng: &mut R,
+) -> Result<(), CollapseError<Sp::Coordinate>> {
+    Solver::new(rule).collapse(space, rng)
+}
 
-    for coordinate in unresolved_set.iter() {
-        to_propagate.push_back(*coordinate);
-    }
-    run_propagation(
-        space,
-        rule,
-        &mut to_propagate,
-        &neighbor_directions,
-        &mut neighbors,
-        &mut neighbor_states,
-    );
+/// Perform the wave function collapse algorithm like [collapse], reporting
+/// progress to `observer` as described in [Solver::collapse_with_progress].
+pub fn collapse_with_progress<
+    Rule: CollapseRule<St, Sp>,
+    St: State,
+    Sp: Space<St>,
+    O: ProgressObserver,
+    R: RngCore,
+>(
+    space: &mut Sp,
+    rule: &Rule,
+    observer: &mut O,
+    rng: &mut R,
+) -> Result<(), CollapseError<Sp::Coordinate>> {
+    Solver::new(rule).collapse_with_progress(space, observer, rng)
+}
 
-    while let Some(to_collapse) = find_next_to_collapse(
-        &mut unresolved_set,
-        &mut lowest_entropy_set,
-        &mut resolved_set,
-        space,
-    ) {
-        to_propagate.clear();
-        space.neighbors(to_collapse, &neighbor_directions, &mut neighbors);
-        for i in 0..neighbor_directions.len() {
-            neighbor_states[i] = neighbors[i].map(|coord| space[coord].clone());
-        }
-        rule.observe(&mut space[to_collapse], &neighbor_states[..]);
-        for i in 0..neighbor_directions.len() {
-            if let Some(neighbor_coord) = neighbors[i] {
-                to_propagate.push_back(neighbor_coord);
-            }
+// Queues every cell that isn't final yet
+fn queue_unresolved<St: State, Sp: Space<St>>(
+    space: &Sp,
+    coordinates: &[Sp::Coordinate],
+    queue: &mut EntropyQueue<Sp::Coordinate>,
+) {
+    for coord in coordinates {
+        let entropy = space[*coord].entropy();
+        if entropy > 0 {
+            queue.push(*coord, entropy);
         }
-        run_propagation(
-            space,
-            rule,
-            &mut to_propagate,
-            &neighbor_directions,
-            &mut neighbors,
-            &mut neighbor_states,
-        );
     }
 }
 
-fn run_propagation<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>>(
+// Observes the cell at `coord`, returning its state from before the observation
+fn observe_cell<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>>(
     space: &mut Sp,
     rule: &Rule,
-    to_propagate: &mut VecDeque<Sp::Coordinate>,
+    coord: Sp::Coordinate,
     neighbor_directions: &[Sp::CoordinateDelta],
     neighbors: &mut [Option<Sp::Coordinate>],
-    neighbor_states: &mut [Option<St>],
-) {
-    while let Some(propagating) = to_propagate.pop_front() {
-        let entropy_before = space[propagating].entropy();
-
-        if entropy_before != 0 {
-            space.neighbors(propagating, neighbor_directions, neighbors);
-            for i in 0..neighbor_directions.len() {
-                neighbor_states[i] = neighbors[i].map(|coord| space[coord].clone());
-            }
-            rule.collapse(&mut space[propagating], neighbor_states);
-            let entropy_after = space[propagating].entropy();
-
-            if entropy_after < entropy_before {
-                for i in 0..neighbor_directions.len() {
-                    if let Some(neighbor) = neighbors[i] {
-                        if space[neighbor].entropy() != 0 {
-                            to_propagate.push_back(neighbor);
-                        }
-                    }
-                }
-            }
-        }
-    }
+    rng: &mut dyn RngCore,
+) -> St {
+    space.neighbors(coord, neighbor_directions, neighbors);
+    let mut cell = space[coord].clone();
+    rule.observe(&mut cell, &Neighbors::new(space, neighbors), rng);
+    std::mem::replace(&mut space[coord], cell)
 }
@@ -0,0 +1,68 @@
+use crate::{CollapseError, CollapseRule, SetState, Solver, Space, State};
+
+/// Pins the cell at `coord` in `space` to the states in `allowed`, as
+/// described in [Solver::pin].
+pub fn pin<Rule: CollapseRule<St, Sp>, St: State + SetState, Sp: Space<St>>(
+    space: &mut Sp,
+    rule: &Rule,
+    coord: Sp::Coordinate,
+    allowed: &St,
+) -> Result<(), CollapseError<Sp::Coordinate>> {
+    Solver::new(rule).pin(space, coord, allowed)
+}
+
+/// Pins every cell in `pins` in order, as described in [Solver::pin_all].
+pub fn pin_all<Rule: CollapseRule<St, Sp>, St: State + SetState, Sp: Space<St>>(
+    space: &mut Sp,
+    rule: &Rule,
+    pins: &[(Sp::Coordinate, St)],
+) -> Result<(), CollapseError<Sp::Coordinate>> {
+    Solver::new(rule).pin_all(space, pins)
+}
+
+impl<St: State + SetState, Sp: Space<St>, Rule: CollapseRule<St, Sp>> Solver<St, Sp, Rule> {
+    /// Pins the cell at `coord` to the states in `allowed`, removing every
+    /// other state from it, and propagates the change through `space`.
+    ///
+    /// Pinning to a single final state fixes the cell to that state, such as
+    /// for entrances or spawn points, while pinning to several states only
+    /// restricts it. Since pins are propagated right away, collapsing the
+    /// space afterwards starts from the pinned states.
+    ///
+    /// The pin is checked against its neighbors immediately, including those
+    /// which are already final. Returns [CollapseError::PinConflict] if it
+    /// can't be satisfied along with earlier pins and the states of the
+    /// space, in which case the space is left partially constrained.
+    pub fn pin(
+        &mut self,
+        space: &mut Sp,
+        coord: Sp::Coordinate,
+        allowed: &St,
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        let conflict = |coordinate| CollapseError::PinConflict {
+            coordinate,
+            pinned: coord,
+        };
+        let mut disallowed = space[coord].clone();
+        disallowed.clear_states(allowed);
+        let mut state = space[coord].clone();
+        state.clear_states(&disallowed);
+        self.replace_cell(space, coord, state);
+
+        self.check_cell(space, coord).map_err(conflict)?;
+        self.propagate_from(space, coord, None).map_err(conflict)
+    }
+
+    /// Pins every cell in `pins` in order with [Solver::pin], stopping at the
+    /// first conflict.
+    pub fn pin_all(
+        &mut self,
+        space: &mut Sp,
+        pins: &[(Sp::Coordinate, St)],
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        for (coord, allowed) in pins {
+            self.pin(space, *coord, allowed)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use crate::{collapse, CollapseError, CollapseRule, Space, State};
+
+/// Summary of a successful [collapse_with_retries] run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RetryOutcome {
+    /// Number of attempts made, including the successful one
+    pub attempts: usize,
+    /// Seed of the successful attempt. Passing `StdRng::seed_from_u64(seed)`
+    /// to [crate::collapse] with the initial space reproduces the result.
+    pub seed: u64,
+}
+
+/// Perform the wave function collapse algorithm like [crate::collapse], but
+/// start over from the initial space whenever a contradiction is found.
+///
+/// This is much cheaper than [crate::collapse_with_backtracking] per attempt,
+/// and works well when contradictions are rare.
+///
+/// * `max_restarts` - The number of times to start over before giving up
+/// * `rng` - Source of the seed for each attempt. Each attempt runs with its
+///   own [StdRng] seeded from this.
+///
+/// Returns the error from the last attempt if every attempt fails, in which
+/// case the space is left as that attempt left it.
+pub fn collapse_with_retries<
+    Rule: CollapseRule<St, Sp>,
+    St: State,
+    Sp: Space<St> + Clone,
+    R: RngCore,
+>(
+    space: &mut Sp,
+    rule: &Rule,
+    max_restarts: usize,
+    rng: &mut R,
+) -> Result<RetryOutcome, CollapseError<Sp::Coordinate>> {
+    let initial = space.clone();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let seed = rng.next_u64();
+        match collapse(space, rule, &mut StdRng::seed_from_u64(seed)) {
+            Ok(()) => return Ok(RetryOutcome { attempts, seed }),
+            Err(err) if attempts > max_restarts => return Err(err),
+            Err(_) => space.clone_from(&initial),
+        }
+    }
+}
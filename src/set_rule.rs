@@ -1,20 +1,30 @@
-use crate::{CollapseRule, Final, InvertDelta, SetState, Space, State};
+use crate::{CollapseRule, Final, InvertDelta, Neighbors, SetState, Space, State};
 use bevy_utils::HashMap;
-use rand::{thread_rng, Rng};
-use std::hash::Hash;
+use rand::{Rng, RngCore};
+use std::{
+    fmt::{self, Debug, Display},
+    hash::Hash,
+};
 
 pub trait SetCollapseObserver<S: State> {
-    fn observe(&self, cell: &mut S, neighbors: &[Option<S>]);
+    fn observe<Sp: Space<S>>(
+        &self,
+        cell: &mut S,
+        neighbors: &Neighbors<S, Sp>,
+        rng: &mut dyn RngCore,
+    );
 }
 
 #[derive(Clone)]
 pub struct UniformSetCollapseObserver;
 
 impl<S: SetState + State + Clone> SetCollapseObserver<S> for UniformSetCollapseObserver {
-    fn observe(&self, cell: &mut S, _: &[Option<S>]) {
+    fn observe<Sp: Space<S>>(&self, cell: &mut S, _: &Neighbors<S, Sp>, rng: &mut dyn RngCore) {
         let mut final_states = Vec::new();
         cell.collect_final_states(&mut final_states);
-        *cell = final_states[thread_rng().gen_range(0..final_states.len())].clone();
+        // Sampled as a u32 rather than a usize, so the same seed picks the same
+        // state on 32 and 64 bit targets
+        *cell = final_states[rng.gen_range(0..final_states.len() as u32) as usize].clone();
     }
 }
 
@@ -29,7 +39,7 @@ pub struct WeightedSetCollapseObserver<T: Eq + Hash + Clone> {
 impl<S: SetState + State + Clone + Final<T>, T: Eq + Hash + Clone> SetCollapseObserver<S>
     for WeightedSetCollapseObserver<T>
 {
-    fn observe(&self, cell: &mut S, _: &[Option<S>]) {
+    fn observe<Sp: Space<S>>(&self, cell: &mut S, _: &Neighbors<S, Sp>, rng: &mut dyn RngCore) {
         let mut final_states = Vec::new();
         cell.collect_final_states(&mut final_states);
 
@@ -44,7 +54,7 @@ impl<S: SetState + State + Clone + Final<T>, T: Eq + Hash + Clone> SetCollapseOb
             weight_vec[i] = *self.weights.get(&state).unwrap() + weight_vec[i - 1];
         }
 
-        let rand = thread_rng().gen_range(0..*weight_vec.last().unwrap());
+        let rand = rng.gen_range(0..*weight_vec.last().unwrap());
         let mut prev = 0;
         for (i, weight) in weight_vec.into_iter().enumerate() {
             if weight >= rand && weight - prev != 0 {
@@ -56,9 +66,26 @@ impl<S: SetState + State + Clone + Final<T>, T: Eq + Hash + Clone> SetCollapseOb
     }
 }
 
+// Rules are compiled into dense adjacency tables when built. Every final state
+// with a rule is a tile, and for each neighbor offset and tile the rule stores a
+// mask of the tiles which allow that tile as their neighbor at that offset. The
+// states a cell is allowed to keep are then the union of the masks of its
+// neighbor's tiles, once per neighbor.
 pub struct SetCollapseRule<S: SetState + State + Sized, Sp: Space<S>, O: SetCollapseObserver<S>> {
     neighbor_offsets: Box<[Sp::CoordinateDelta]>,
-    state_rules: Box<[(S, Box<[Option<S>]>)]>,
+    // The final state of each tile
+    tiles: Box<[S]>,
+    // The tile of each final state index, for states supporting
+    // [SetState::for_each_index]
+    tile_indices: Box<[Option<usize>]>,
+    // Mask of the tiles allowing each tile as their neighbor, indexed by
+    // `offset * tiles.len() + tile`
+    allowed_by: Box<[S]>,
+    // Every tile, which are the only states rules can remove
+    all_tiles: S,
+    // For each neighbor offset, the tiles which can't face the outside of the
+    // space in that direction, if it has a boundary constraint
+    boundaries: Box<[Option<S>]>,
     observer: O,
 }
 
@@ -80,6 +107,93 @@ impl<S: SetState + Clone> StateRule<S> {
     }
 }
 
+/// A problem with a rule set, as found by [SetCollapseRuleBuilder::validate].
+///
+/// States are always final states.
+#[derive(Clone, PartialEq, Debug)]
+pub enum RuleDiagnostic<S, D> {
+    /// `state` allows `neighbor` at `delta`, but `neighbor` doesn't allow
+    /// `state` at the inverse of `delta`, so the pair can never be placed
+    Asymmetric { state: S, delta: D, neighbor: S },
+    /// `state` doesn't allow any neighbor at `delta`, so it can only be placed
+    /// where that neighbor is outside of the space
+    NoAllowedNeighbor { state: S, delta: D },
+    /// No other state allows `state` as its neighbor in any direction
+    Unreachable { state: S },
+    /// `state` has rules but isn't part of `all_state`
+    UnknownState { state: S },
+    /// `state` allows `neighbor` at `delta`, but `neighbor` isn't part of
+    /// `all_state`
+    UnknownNeighbor { state: S, delta: D, neighbor: S },
+}
+
+impl<S, D> RuleDiagnostic<S, D> {
+    /// Converts every state in the diagnostic with `f`, such as to look up the
+    /// names of tiles before printing it.
+    pub fn map_states<T>(self, mut f: impl FnMut(S) -> T) -> RuleDiagnostic<T, D> {
+        match self {
+            Self::Asymmetric {
+                state,
+                delta,
+                neighbor,
+            } => RuleDiagnostic::Asymmetric {
+                state: f(state),
+                delta,
+                neighbor: f(neighbor),
+            },
+            Self::NoAllowedNeighbor { state, delta } => RuleDiagnostic::NoAllowedNeighbor {
+                state: f(state),
+                delta,
+            },
+            Self::Unreachable { state } => RuleDiagnostic::Unreachable { state: f(state) },
+            Self::UnknownState { state } => RuleDiagnostic::UnknownState { state: f(state) },
+            Self::UnknownNeighbor {
+                state,
+                delta,
+                neighbor,
+            } => RuleDiagnostic::UnknownNeighbor {
+                state: f(state),
+                delta,
+                neighbor: f(neighbor),
+            },
+        }
+    }
+}
+
+impl<S: Debug, D: Debug> Display for RuleDiagnostic<S, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Asymmetric {
+                state,
+                delta,
+                neighbor,
+            } => write!(
+                f,
+                "{:?} allows {:?} at {:?}, but not the other way around",
+                state, neighbor, delta
+            ),
+            Self::NoAllowedNeighbor { state, delta } => {
+                write!(f, "{:?} has no allowed neighbors at {:?}", state, delta)
+            }
+            Self::Unreachable { state } => {
+                write!(f, "{:?} isn't allowed next to any other state", state)
+            }
+            Self::UnknownState { state } => {
+                write!(f, "{:?} has rules but isn't in all_state", state)
+            }
+            Self::UnknownNeighbor {
+                state,
+                delta,
+                neighbor,
+            } => write!(
+                f,
+                "{:?} allows {:?} at {:?}, which isn't in all_state",
+                state, neighbor, delta
+            ),
+        }
+    }
+}
+
 // Builder for SetCollapseRule
 pub struct SetCollapseRuleBuilder<
     S: SetState + State,
@@ -88,6 +202,7 @@ pub struct SetCollapseRuleBuilder<
 > {
     neighbor_offsets: Vec<Sp::CoordinateDelta>,
     state_rules: Vec<StateRule<S>>,
+    boundaries: Vec<Option<S>>,
     observer: O,
     all_state: S,
 }
@@ -101,6 +216,7 @@ where
         Self {
             neighbor_offsets: Vec::new(),
             state_rules: Vec::new(),
+            boundaries: Vec::new(),
             observer,
             all_state,
         }
@@ -108,7 +224,8 @@ where
 
     // Set the allowed neighbors for a cell based on their coordinate deltas
     //
-    // Rules aren't added symmetrically - only provided rules will be added
+    // Rules aren't added symmetrically - only provided rules will be added.
+    // Use `allow_symmetric` to add the reverse rules as well.
     //
     // States which do not have any allowed neighbors for a given coordinate
     // delta will require that those coordinates are outside of world-space.
@@ -128,6 +245,127 @@ where
         self
     }
 
+    // Set the allowed neighbors for a cell like `allow`, and also allow the
+    // cell as a neighbor of each of those neighbors in the opposite direction
+    //
+    // For each `(delta, neighbor)`, this adds both `state` allowing `neighbor`
+    // at `delta`, and `neighbor` allowing `state` at `delta.invert_delta()`,
+    // so adjacency only needs to be listed from one side.
+    pub fn allow_symmetric(mut self, state: &S, neighbors: &[(Sp::CoordinateDelta, S)]) -> Self {
+        self = self.allow(state, neighbors);
+        for (delta, neighbor) in neighbors {
+            self = self.allow(neighbor, &[(delta.invert_delta(), state.clone())]);
+        }
+        self
+    }
+
+    // Set the states allowed to face the outside of the space in the direction
+    // of a coordinate delta, where the neighbor at that delta is out of bounds
+    //
+    // Cells on that edge of the space lose every other state. Calling this
+    // more than once for the same delta allows the states from every call.
+    // Without a boundary constraint, any state can face the outside.
+    //
+    // Adding a boundary for a delta without any allowed neighbors also
+    // requires every state to be on that edge, as with `allow`.
+    pub fn boundary(mut self, delta: Sp::CoordinateDelta, allowed: &S) -> Self {
+        let offset_index = self.get_offset_index(delta);
+        if self.boundaries.len() <= offset_index {
+            self.boundaries.resize(offset_index + 1, None);
+        }
+        if let Some(boundary) = &mut self.boundaries[offset_index] {
+            boundary.set_states(allowed);
+        } else {
+            self.boundaries[offset_index] = Some(allowed.clone());
+        }
+        self
+    }
+
+    // Checks the rules added so far for likely mistakes, returning a
+    // diagnostic for each one found
+    //
+    // Reports asymmetric adjacencies, states without any allowed neighbor at
+    // some delta, states no other state allows next to it, and states missing
+    // from `all_state`. None of these stop the rule from being built, but
+    // they often lead to contradictions or states which never appear.
+    pub fn validate(&self) -> Vec<RuleDiagnostic<S, Sp::CoordinateDelta>> {
+        let mut diagnostics = Vec::new();
+        let mut states = Vec::new();
+        self.all_state.collect_final_states(&mut states);
+        for rule in &self.state_rules {
+            if !self.all_state.has_any_of(&rule.state) {
+                diagnostics.push(RuleDiagnostic::UnknownState {
+                    state: rule.state.clone(),
+                });
+                states.push(rule.state.clone());
+            }
+        }
+
+        for state in &states {
+            for (offset, delta) in self.neighbor_offsets.iter().enumerate() {
+                let Some(allowed) = self.allowed(state, offset) else {
+                    diagnostics.push(RuleDiagnostic::NoAllowedNeighbor {
+                        state: state.clone(),
+                        delta: delta.clone(),
+                    });
+                    continue;
+                };
+                let mut neighbors = Vec::new();
+                allowed.collect_final_states(&mut neighbors);
+                let inverse = self
+                    .neighbor_offsets
+                    .iter()
+                    .position(|other| *other == delta.invert_delta());
+                for neighbor in neighbors {
+                    if !self.all_state.has_any_of(&neighbor) {
+                        diagnostics.push(RuleDiagnostic::UnknownNeighbor {
+                            state: state.clone(),
+                            delta: delta.clone(),
+                            neighbor,
+                        });
+                        continue;
+                    }
+                    let symmetric = inverse
+                        .and_then(|inverse| self.allowed(&neighbor, inverse))
+                        .is_some_and(|allowed| allowed.has_any_of(state));
+                    if !symmetric {
+                        diagnostics.push(RuleDiagnostic::Asymmetric {
+                            state: state.clone(),
+                            delta: delta.clone(),
+                            neighbor,
+                        });
+                    }
+                }
+            }
+        }
+
+        for state in &states {
+            let reachable = self.state_rules.iter().any(|rule| {
+                rule.state != *state
+                    && rule
+                        .allowed_neighbors
+                        .iter()
+                        .flatten()
+                        .any(|allowed| allowed.has_any_of(state))
+            });
+            if !reachable {
+                diagnostics.push(RuleDiagnostic::Unreachable {
+                    state: state.clone(),
+                });
+            }
+        }
+        diagnostics
+    }
+
+    // The states allowed next to `state` at the offset with index `offset`
+    fn allowed(&self, state: &S, offset: usize) -> Option<&S> {
+        self.state_rules
+            .iter()
+            .find(|rule| rule.state == *state)
+            .and_then(|rule| rule.allowed_neighbors.get(offset))
+            .and_then(Option::as_ref)
+    }
+
     fn get_offset_index(&mut self, offset: Sp::CoordinateDelta) -> usize {
         for i in 0..self.neighbor_offsets.len() {
             if self.neighbor_offsets[i] == offset {
@@ -154,34 +392,117 @@ where
     }
 
     pub fn build(self) -> SetCollapseRule<S, Sp, O> {
-        let mut state_rules = Vec::new();
+        let offsets = self.neighbor_offsets.len();
+        let mut rules = Vec::new();
         let mut remaining_state = self.all_state;
-        for mut proto_rule in self.state_rules {
-            while proto_rule.allowed_neighbors.len() < self.neighbor_offsets.len() {
-                proto_rule.allowed_neighbors.push(None);
-            }
+        for proto_rule in self.state_rules {
             remaining_state.clear_states(&proto_rule.state);
-            state_rules.push((
-                proto_rule.state,
-                proto_rule.allowed_neighbors.into_boxed_slice(),
-            ));
+            rules.push((proto_rule.state, proto_rule.allowed_neighbors));
         }
         let mut remaining_states = Vec::new();
         remaining_state.collect_final_states(&mut remaining_states);
-        for remaining_state in remaining_states {
-            state_rules.push((
-                remaining_state,
-                vec![None; self.neighbor_offsets.len()].into_boxed_slice(),
-            ));
+        rules.extend(
+            remaining_states
+                .into_iter()
+                .map(|state| (state, Vec::new())),
+        );
+
+        let mut empty = remaining_state;
+        empty.clear_states(&empty.clone());
+        let mut all_tiles = empty.clone();
+        for (state, _) in &rules {
+            all_tiles.set_states(state);
+        }
+
+        let mut tile_indices = Vec::new();
+        for (tile, (state, _)) in rules.iter().enumerate() {
+            state.for_each_index(|index| {
+                if tile_indices.len() <= index {
+                    tile_indices.resize(index + 1, None);
+                }
+                tile_indices[index] = Some(tile);
+            });
         }
+
+        let tiles = rules.len();
+        let mut allowed_by = vec![empty.clone(); offsets * tiles].into_boxed_slice();
+        for (state, allowed_neighbors) in &rules {
+            for (offset, allowed) in allowed_neighbors.iter().enumerate() {
+                let Some(allowed) = allowed else {
+                    continue;
+                };
+                for (tile, (tile_state, _)) in rules.iter().enumerate() {
+                    if allowed.has_any_of(tile_state) {
+                        allowed_by[offset * tiles + tile].set_states(state);
+                    }
+                }
+            }
+        }
+
+        let mut boundaries = self.boundaries;
+        boundaries.resize(offsets, None);
+        for boundary in boundaries.iter_mut().flatten() {
+            let mut disallowed = all_tiles.clone();
+            disallowed.clear_states(boundary);
+            *boundary = disallowed;
+        }
+
         SetCollapseRule {
             neighbor_offsets: self.neighbor_offsets.into_boxed_slice(),
-            state_rules: state_rules.into_boxed_slice(),
+            tiles: rules.into_iter().map(|(state, _)| state).collect(),
+            tile_indices: tile_indices.into_boxed_slice(),
+            allowed_by,
+            all_tiles,
+            boundaries: boundaries.into_boxed_slice(),
             observer: self.observer,
         }
     }
 }
 
+impl<S: SetState + State, Sp: Space<S>, O: SetCollapseObserver<S>> SetCollapseRule<S, Sp, O> {
+    // The final state of every tile with a rule
+    pub(crate) fn tiles(&self) -> &[S] {
+        &self.tiles
+    }
+
+    // The tiles which allow `tile` as their neighbor at the neighbor offset
+    // with index `offset`
+    pub(crate) fn allowed_by(&self, offset: usize, tile: usize) -> &S {
+        &self.allowed_by[offset * self.tiles.len() + tile]
+    }
+
+    // Sets `disallowed` to the tiles which aren't allowed next to
+    // `neighbor_state` at the neighbor offset with index `offset`. Tiles
+    // which aren't in `cell` may be left in it.
+    fn disallowed_next_to(&self, offset: usize, neighbor_state: &S, cell: &S, disallowed: &mut S) {
+        // Start from every tile, and remove the mask of each neighbor tile
+        let tiles = self.tiles.len();
+        disallowed.clone_from(&self.all_tiles);
+        let masks = &self.allowed_by[offset * tiles..(offset + 1) * tiles];
+        let indexed = neighbor_state.for_each_index(|index| {
+            if let Some(Some(tile)) = self.tile_indices.get(index) {
+                disallowed.clear_states(&masks[*tile]);
+            }
+        });
+        if !indexed {
+            for (tile_state, mask) in self.tiles.iter().zip(masks) {
+                if neighbor_state.has_any_of(tile_state) {
+                    disallowed.clear_states(mask);
+                    if !cell.has_any_of(disallowed) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // The tiles which can't face the outside of the space at the neighbor
+    // offset with index `offset`, if there's a boundary constraint for it
+    pub(crate) fn boundary_disallowed(&self, offset: usize) -> Option<&S> {
+        self.boundaries[offset].as_ref()
+    }
+}
+
 // A collapse rule implementation that works with implementors of [crate::SetState<T>]
 impl<S: SetState + State, Sp: Space<S>, O: SetCollapseObserver<S>> CollapseRule<S, Sp>
     for SetCollapseRule<S, Sp, O>
@@ -192,26 +513,39 @@ where
         self.neighbor_offsets.clone()
     }
 
-    fn collapse(&self, cell: &mut S, neighbors: &[Option<S>]) {
-        for (state, allowed_neighbors) in &self.state_rules[..] {
-            if cell.has_any_of(state) {
-                for i in 0..neighbors.len() {
-                    if let Some(neighbor_state) = &neighbors[i] {
-                        let allow = if let Some(allowed_state) = &allowed_neighbors[i] {
-                            neighbor_state.has_any_of(allowed_state)
-                        } else {
-                            false
-                        };
-                        if !allow {
-                            cell.clear_states(state)
-                        }
-                    }
+    fn collapse(&self, cell: &mut S, neighbors: &Neighbors<S, Sp>) {
+        let mut disallowed = self.all_tiles.clone();
+        for (offset, neighbor_state) in neighbors.iter().enumerate() {
+            let Some(neighbor_state) = neighbor_state else {
+                if let Some(boundary) = &self.boundaries[offset] {
+                    cell.clear_states(boundary);
+                }
+                continue;
+            };
+            self.disallowed_next_to(offset, neighbor_state, cell, &mut disallowed);
+            cell.clear_states(&disallowed);
+        }
+    }
+
+    fn conflicts(&self, cell: &S, neighbors: &Neighbors<S, Sp>, conflicts: &mut Vec<usize>) {
+        let mut disallowed = self.all_tiles.clone();
+        for (offset, neighbor_state) in neighbors.iter().enumerate() {
+            let conflict = match neighbor_state {
+                None => self.boundaries[offset]
+                    .as_ref()
+                    .is_some_and(|boundary| cell.has_any_of(boundary)),
+                Some(neighbor_state) => {
+                    self.disallowed_next_to(offset, neighbor_state, cell, &mut disallowed);
+                    cell.has_any_of(&disallowed)
                 }
+            };
+            if conflict {
+                conflicts.push(offset);
             }
         }
     }
 
-    fn observe(&self, cell: &mut S, neighbors: &[Option<S>]) {
-        self.observer.observe(cell, neighbors);
+    fn observe(&self, cell: &mut S, neighbors: &Neighbors<S, Sp>, rng: &mut dyn RngCore) {
+        self.observer.observe(cell, neighbors, rng);
     }
 }
@@ -10,6 +10,16 @@ pub trait SetState: Sized {
     fn clear_states(&mut self, states: &Self);
     /// Separates out all the final (0-entropy) states from this state into a Vec
     fn collect_final_states(&self, states: &mut Vec<Self>);
+    /// Calls `f` with the index of every final state in `self`, for state
+    /// types which identify final states by a dense index such as a bit
+    /// position. Returns `false` without calling `f` if they don't.
+    ///
+    /// Rules use this to look up precomputed tables by index instead of
+    /// testing every known state against `self`.
+    fn for_each_index(&self, f: impl FnMut(usize)) -> bool {
+        let _ = f;
+        false
+    }
 }
 
 pub trait Final<T: Eq + Hash + Clone> {
@@ -0,0 +1,652 @@
+use rand::RngCore;
+use std::{
+    collections::VecDeque,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    observe_cell, queue_unresolved, CollapseError, CollapseRule, EntropyQueue, Neighbors, Space,
+    State,
+};
+
+/// A single step of a [Solver], as returned by [Solver::step]
+#[derive(Clone, PartialEq, Debug)]
+pub enum Step<C, S> {
+    /// The cell at `coord` was observed, leaving it in `state`
+    Observed { coord: C, state: S },
+    /// Changes were propagated through the space, from either the initial
+    /// states or the last observation. `changed` holds the coordinate of every
+    /// cell whose state changed, in ascending order.
+    Propagated { changed: Vec<C> },
+    /// Every cell is final. Further steps keep returning this until the
+    /// solver is restarted.
+    Finished,
+    /// A contradiction was found, leaving the space partially collapsed.
+    /// Further steps keep returning this until the solver is restarted.
+    Contradiction(CollapseError<C>),
+}
+
+/// Limits on how long [Solver::collapse_within] and [Solver::resume_within]
+/// run for. Solving stops as soon as any of them is reached.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Limits<'a> {
+    /// Stop once this flag is set, for example from another thread
+    pub cancel: Option<&'a AtomicBool>,
+    /// Stop once this much time has passed
+    pub time: Option<Duration>,
+}
+
+impl<'a> Limits<'a> {
+    /// Limits which are never reached
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Stop once `cancel` is set
+    pub fn with_cancel(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Stop once `time` has passed
+    pub fn with_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// Progress of a [Solver] run, as reported to a [ProgressObserver]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Progress {
+    /// Number of cells which are final
+    pub resolved: usize,
+    /// Number of cells in the space
+    pub total: usize,
+    /// Number of times the rule has been applied to a cell while propagating
+    pub propagation_steps: usize,
+    /// Lowest entropy of any cell which isn't final yet, or `None` if every
+    /// cell is final
+    pub min_entropy: Option<u32>,
+}
+
+impl Progress {
+    /// The fraction of cells which are final, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.resolved as f32 / self.total as f32
+    }
+}
+
+/// Receives progress updates from [Solver::collapse_with_progress], such as
+/// to drive a progress bar.
+///
+/// Implemented for closures taking a [Progress].
+pub trait ProgressObserver {
+    fn progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressObserver for F {
+    fn progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// How a call to [Solver::collapse_within] or [Solver::resume_within] ended
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollapseStatus {
+    /// Every cell is final
+    Finished,
+    /// A limit was reached before every cell was final. Calling
+    /// [Solver::resume_within] with the same space resumes the run.
+    Interrupted,
+}
+
+// Where the current run of a [Solver] is up to
+enum Phase<C> {
+    Start,
+    Ready,
+    Observed(C),
+    // Propagation was interrupted by a limit, after observing the cell if any,
+    // or otherwise while propagating the initial states
+    Propagating(Option<C>),
+    Done(Option<CollapseError<C>>),
+}
+
+// Number of cells propagated between checks of the limits, since reading the
+// clock for every cell would slow propagation down
+const LIMIT_CHECK_INTERVAL: usize = 64;
+
+// The limits of a single call to [Solver::resume_within]
+struct Stop<'a> {
+    cancel: Option<&'a AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Stop<'a> {
+    fn new(limits: Limits<'a>) -> Self {
+        Self {
+            cancel: limits.cancel,
+            deadline: limits.time.map(|time| Instant::now() + time),
+        }
+    }
+
+    fn reached(&self) -> bool {
+        let cancelled = self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+        let expired = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        cancelled || expired
+    }
+}
+
+/// Reusable wave function collapse solver.
+///
+/// Owns a collapse rule along with every buffer used while collapsing, so
+/// collapsing many spaces one after another doesn't reallocate them each
+/// time. [crate::collapse] is a shorthand for running a new solver once.
+///
+/// ```ignore
+/// let mut solver = Solver::new(rule);
+/// for room in &mut rooms {
+///     solver.collapse(room, &mut rng)?;
+/// }
+/// ```
+///
+/// Solving can also be driven a step at a time with [Solver::step], for
+/// example to show generation as it happens.
+pub struct Solver<St: State, Sp: Space<St>, Rule: CollapseRule<St, Sp>> {
+    rule: Rule,
+    neighbor_directions: Box<[Sp::CoordinateDelta]>,
+    neighbors: Box<[Option<Sp::Coordinate>]>,
+    coordinates: Vec<Sp::Coordinate>,
+    pub(crate) queue: EntropyQueue<Sp::Coordinate>,
+    to_propagate: VecDeque<Sp::Coordinate>,
+    changes: Vec<(Sp::Coordinate, St)>,
+    resolved: usize,
+    propagation_steps: usize,
+    phase: Phase<Sp::Coordinate>,
+    _state: PhantomData<fn() -> St>,
+}
+
+impl<St: State, Sp: Space<St>, Rule: CollapseRule<St, Sp>> Solver<St, Sp, Rule> {
+    /// Creates a new solver for `rule`
+    pub fn new(rule: Rule) -> Self {
+        let neighbor_directions = rule.neighbor_offsets();
+        let neighbors = vec![None; neighbor_directions.len()].into_boxed_slice();
+        Self {
+            rule,
+            neighbor_directions,
+            neighbors,
+            coordinates: Vec::new(),
+            queue: EntropyQueue::new(),
+            to_propagate: VecDeque::new(),
+            changes: Vec::new(),
+            resolved: 0,
+            propagation_steps: 0,
+            phase: Phase::Start,
+            _state: PhantomData,
+        }
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// Consumes the solver, returning its rule
+    pub fn into_rule(self) -> Rule {
+        self.rule
+    }
+
+    /// Perform the wave function collapse algorithm on `space`, as with
+    /// [crate::collapse].
+    ///
+    /// This always starts a new run, abandoning any run driven by
+    /// [Solver::step].
+    pub fn collapse<R: RngCore>(
+        &mut self,
+        space: &mut Sp,
+        rng: &mut R,
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        self.restart();
+        self.propagate_initial(space, None, rng)?;
+
+        while let Some(to_collapse) = self.queue.pop(space) {
+            let contradiction = |coordinate| CollapseError::Contradiction {
+                coordinate,
+                last_observed: Some(to_collapse),
+            };
+            self.observe(space, to_collapse, rng);
+            if space[to_collapse].is_contradiction() {
+                return Err(contradiction(to_collapse));
+            }
+            self.propagate_observed(space, None)
+                .map_err(contradiction)?;
+        }
+        Ok(())
+    }
+
+    /// Perform the wave function collapse algorithm on `space` like
+    /// [Solver::collapse], reporting progress to `observer` once the initial
+    /// states are propagated and after every observation.
+    pub fn collapse_with_progress<R: RngCore>(
+        &mut self,
+        space: &mut Sp,
+        observer: &mut impl ProgressObserver,
+        rng: &mut R,
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        self.restart();
+        loop {
+            match self.advance(space, rng, false, None) {
+                Step::Finished => break,
+                Step::Contradiction(err) => return Err(err),
+                Step::Observed { .. } => {}
+                Step::Propagated { .. } => observer.progress(&self.progress(space)),
+            }
+        }
+        observer.progress(&self.progress(space));
+        Ok(())
+    }
+
+    /// Gets the progress of the current run on `space`
+    pub fn progress(&mut self, space: &Sp) -> Progress {
+        Progress {
+            resolved: self.resolved,
+            total: self.coordinates.len(),
+            propagation_steps: self.propagation_steps,
+            min_entropy: self.queue.min_entropy(space),
+        }
+    }
+
+    /// Starts a new run for [Solver::step]. The next step propagates the
+    /// initial states of the space it's given.
+    pub fn restart(&mut self) {
+        self.phase = Phase::Start;
+    }
+
+    /// Advances the solver by a single step on `space`.
+    ///
+    /// The first step of a run propagates the initial states of the space.
+    /// After that, steps alternate between observing the cell with the lowest
+    /// entropy and propagating the changes from that observation, until every
+    /// cell is final or a contradiction is found. Taken together, the steps
+    /// of a run make the same changes as [Solver::collapse] given an
+    /// identically seeded rng.
+    ///
+    /// Every step of a run must be given the same space, unmodified between
+    /// steps. Call [Solver::restart] before stepping a different space.
+    pub fn step<R: RngCore>(&mut self, space: &mut Sp, rng: &mut R) -> Step<Sp::Coordinate, St> {
+        self.advance(space, rng, true, None)
+    }
+
+    /// Perform the wave function collapse algorithm on `space` like
+    /// [Solver::collapse], but stop early once any of `limits` is reached.
+    ///
+    /// Limits are checked after every step and every few cells while
+    /// propagating, so even propagating the initial states of a large space
+    /// can be interrupted. Each call makes some progress. When interrupted, the space is left partially collapsed and the run is
+    /// resumed with [Solver::resume_within].
+    ///
+    /// This always starts a new run, abandoning any run in progress.
+    pub fn collapse_within<R: RngCore>(
+        &mut self,
+        space: &mut Sp,
+        limits: Limits,
+        rng: &mut R,
+    ) -> Result<CollapseStatus, CollapseError<Sp::Coordinate>> {
+        self.restart();
+        self.resume_within(space, limits, rng)
+    }
+
+    /// Continues the run in progress on `space` like
+    /// [Solver::collapse_within], such as one which was interrupted or driven
+    /// by [Solver::step]. `space` must be the space that run was started on,
+    /// left unmodified since. A new run is started if there's no run in
+    /// progress.
+    pub fn resume_within<R: RngCore>(
+        &mut self,
+        space: &mut Sp,
+        limits: Limits,
+        rng: &mut R,
+    ) -> Result<CollapseStatus, CollapseError<Sp::Coordinate>> {
+        if let Phase::Done(_) = self.phase {
+            self.restart();
+        }
+        let stop = Stop::new(limits);
+        loop {
+            match self.advance(space, rng, false, Some(&stop)) {
+                Step::Finished => return Ok(CollapseStatus::Finished),
+                Step::Contradiction(err) => return Err(err),
+                Step::Observed { .. } | Step::Propagated { .. } => {}
+            }
+            if stop.reached() {
+                return Ok(CollapseStatus::Interrupted);
+            }
+        }
+    }
+
+    // Advances the current run by a single step. The cells changed by
+    // propagation are only recorded when `record` is set. Propagation stops
+    // early once `stop` is reached, and is continued by the next step.
+    fn advance(
+        &mut self,
+        space: &mut Sp,
+        rng: &mut dyn RngCore,
+        record: bool,
+        stop: Option<&Stop>,
+    ) -> Step<Sp::Coordinate, St> {
+        match self.phase {
+            Phase::Start => match self.start_initial(space, rng) {
+                Ok(()) => self.continue_propagation(space, None, record, stop),
+                Err(err) => self.finish(Some(err)),
+            },
+            Phase::Ready => {
+                let Some(to_collapse) = self.queue.pop(space) else {
+                    return self.finish(None);
+                };
+                self.observe(space, to_collapse, rng);
+                if space[to_collapse].is_contradiction() {
+                    return self.finish(Some(CollapseError::Contradiction {
+                        coordinate: to_collapse,
+                        last_observed: Some(to_collapse),
+                    }));
+                }
+                self.phase = Phase::Observed(to_collapse);
+                Step::Observed {
+                    coord: to_collapse,
+                    state: space[to_collapse].clone(),
+                }
+            }
+            Phase::Observed(observed) => {
+                self.to_propagate.clear();
+                self.to_propagate.extend(self.neighbors.iter().flatten());
+                self.continue_propagation(space, Some(observed), record, stop)
+            }
+            Phase::Propagating(observed) => {
+                self.continue_propagation(space, observed, record, stop)
+            }
+            Phase::Done(None) => Step::Finished,
+            Phase::Done(Some(err)) => Step::Contradiction(err),
+        }
+    }
+
+    // Propagates the cells left to propagate after observing `observed`, or
+    // the initial states if it's `None`. If `stop` is reached first, the run
+    // is left to continue propagating on the next step.
+    fn continue_propagation(
+        &mut self,
+        space: &mut Sp,
+        observed: Option<Sp::Coordinate>,
+        record: bool,
+        stop: Option<&Stop>,
+    ) -> Step<Sp::Coordinate, St> {
+        let mut changes = std::mem::take(&mut self.changes);
+        let journal = record.then_some(&mut changes);
+        let result = self.propagate(space, journal, stop);
+        self.changes = changes;
+        if let Err(coordinate) = result {
+            return self.finish(Some(CollapseError::Contradiction {
+                coordinate,
+                last_observed: observed,
+            }));
+        }
+
+        if !self.to_propagate.is_empty() {
+            self.phase = Phase::Propagating(observed);
+        } else {
+            if observed.is_none() {
+                queue_unresolved(space, &self.coordinates, &mut self.queue);
+            }
+            self.phase = Phase::Ready;
+        }
+        Step::Propagated {
+            changed: self.take_changed(),
+        }
+    }
+
+    // Ends the current run, returning its final step
+    fn finish(&mut self, err: Option<CollapseError<Sp::Coordinate>>) -> Step<Sp::Coordinate, St> {
+        self.changes.clear();
+        self.phase = Phase::Done(err);
+        match err {
+            None => Step::Finished,
+            Some(err) => Step::Contradiction(err),
+        }
+    }
+
+    // Takes the coordinates of the cells changed since the last call,
+    // dropping their previous states
+    fn take_changed(&mut self) -> Vec<Sp::Coordinate> {
+        if self.changes.is_empty() {
+            return Vec::new();
+        }
+        let mut changed = self
+            .changes
+            .drain(..)
+            .map(|(coord, _)| coord)
+            .collect::<Vec<_>>();
+        changed.sort_unstable();
+        changed.dedup();
+        changed
+    }
+
+    // Clears the queues, then propagates the initial states of the space and
+    // queues every cell that isn't final yet.
+    pub(crate) fn propagate_initial(
+        &mut self,
+        space: &mut Sp,
+        journal: Option<&mut Vec<(Sp::Coordinate, St)>>,
+        rng: &mut dyn RngCore,
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        self.start_initial(space, rng)?;
+        self.propagate(space, journal, None).map_err(|coordinate| {
+            CollapseError::Contradiction {
+                coordinate,
+                last_observed: None,
+            }
+        })?;
+        queue_unresolved(space, &self.coordinates, &mut self.queue);
+        Ok(())
+    }
+
+    // Clears the queues and reads the coordinates of the space, then queues
+    // every cell that isn't final yet to be propagated
+    fn start_initial(
+        &mut self,
+        space: &mut Sp,
+        rng: &mut dyn RngCore,
+    ) -> Result<(), CollapseError<Sp::Coordinate>> {
+        self.queue.reset(rng);
+        self.to_propagate.clear();
+        self.resolved = 0;
+        self.propagation_steps = 0;
+        // Read into the kept buffer every run, since spaces of the same size
+        // can still have different shapes
+        self.coordinates.clear();
+        self.coordinates.extend_from_slice(&space.coordinate_list());
+        for coord in &self.coordinates {
+            if space[*coord].is_contradiction() {
+                return Err(CollapseError::Contradiction {
+                    coordinate: *coord,
+                    last_observed: None,
+                });
+            }
+            if is_resolved(&space[*coord]) {
+                self.resolved += 1;
+            } else {
+                self.to_propagate.push_back(*coord);
+            }
+        }
+        Ok(())
+    }
+
+    // Observes the cell at `coord`, returning its state from before the
+    // observation. Its neighbors are left in the neighbor buffer, ready for
+    // [Solver::propagate_observed].
+    pub(crate) fn observe(
+        &mut self,
+        space: &mut Sp,
+        coord: Sp::Coordinate,
+        rng: &mut dyn RngCore,
+    ) -> St {
+        let previous = observe_cell(
+            space,
+            &self.rule,
+            coord,
+            &self.neighbor_directions,
+            &mut self.neighbors,
+            rng,
+        );
+        if !is_resolved(&previous) && is_resolved(&space[coord]) {
+            self.resolved += 1;
+        }
+        previous
+    }
+
+    // Replaces the state of the cell at `coord`, queueing it if it isn't
+    // final, and returns its previous state
+    pub(crate) fn replace_cell(&mut self, space: &mut Sp, coord: Sp::Coordinate, state: St) -> St {
+        let entropy = state.entropy();
+        match (is_resolved(&space[coord]), is_resolved(&state)) {
+            (true, false) => self.resolved -= 1,
+            (false, true) => self.resolved += 1,
+            _ => {}
+        }
+        if entropy > 0 {
+            self.queue.push(coord, entropy);
+        }
+        std::mem::replace(&mut space[coord], state)
+    }
+
+    // Propagates the changes from the last observation to its neighbors
+    pub(crate) fn propagate_observed(
+        &mut self,
+        space: &mut Sp,
+        journal: Option<&mut Vec<(Sp::Coordinate, St)>>,
+    ) -> Result<(), Sp::Coordinate> {
+        self.to_propagate.clear();
+        self.to_propagate.extend(self.neighbors.iter().flatten());
+        self.propagate(space, journal, None)
+    }
+
+    // Propagates a change to the cell at `coord` to it and its neighbors
+    pub(crate) fn propagate_from(
+        &mut self,
+        space: &mut Sp,
+        coord: Sp::Coordinate,
+        journal: Option<&mut Vec<(Sp::Coordinate, St)>>,
+    ) -> Result<(), Sp::Coordinate> {
+        self.to_propagate.clear();
+        self.to_propagate.push_back(coord);
+        space.neighbors(coord, &self.neighbor_directions, &mut self.neighbors);
+        self.to_propagate.extend(self.neighbors.iter().flatten());
+        self.propagate(space, journal, None)
+    }
+
+    // Applies the rule to the cell at `coord` and each of its neighbors which
+    // is already final, returning the first cell left without any states.
+    // Unlike propagation, this also checks cells which are final.
+    pub(crate) fn check_cell(
+        &mut self,
+        space: &mut Sp,
+        coord: Sp::Coordinate,
+    ) -> Result<(), Sp::Coordinate> {
+        space.neighbors(coord, &self.neighbor_directions, &mut self.neighbors);
+        let final_neighbors = self
+            .neighbors
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|neighbor| space[*neighbor].entropy() == 0)
+            .collect::<Vec<_>>();
+        for checked in std::iter::once(coord).chain(final_neighbors) {
+            space.neighbors(checked, &self.neighbor_directions, &mut self.neighbors);
+            let mut cell = space[checked].clone();
+            self.rule
+                .collapse(&mut cell, &Neighbors::new(space, &self.neighbors));
+            self.replace_cell(space, checked, cell);
+            if space[checked].is_contradiction() {
+                return Err(checked);
+            }
+        }
+        Ok(())
+    }
+
+    // Propagates changes until no more cells change, returning the coordinate
+    // of the first cell left without any possible states. Cells whose entropy
+    // drops are queued again with their new entropy.
+    //
+    // When a journal is given, the previous state of every changed cell is
+    // pushed onto it so the changes can be undone.
+    //
+    // Once `stop` is reached, this returns early with the cells left to
+    // propagate still queued.
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        mut journal: Option<&mut Vec<(Sp::Coordinate, St)>>,
+        stop: Option<&Stop>,
+    ) -> Result<(), Sp::Coordinate> {
+        let mut popped = 0;
+        while let Some(propagating) = self.to_propagate.pop_front() {
+            popped += 1;
+            if popped % LIMIT_CHECK_INTERVAL == 0 && stop.is_some_and(Stop::reached) {
+                self.to_propagate.push_front(propagating);
+                return Ok(());
+            }
+            let entropy_before = space[propagating].entropy();
+            space.neighbors(propagating, &self.neighbor_directions, &mut self.neighbors);
+
+            // Final cells can't change any further, unless they're their own
+            // neighbor, such as on a wrapping axis of size 1. Then any change
+            // to the cell changed its neighbor too, so it's checked again.
+            if entropy_before == 0 && !self.neighbors.contains(&Some(propagating)) {
+                continue;
+            }
+
+            // Only the cell being collapsed is copied, since the space can't
+            // be borrowed mutably while its neighbors are being read
+            let mut cell = space[propagating].clone();
+            self.propagation_steps += 1;
+            self.rule
+                .collapse(&mut cell, &Neighbors::new(space, &self.neighbors));
+            let entropy_after = cell.entropy();
+
+            if entropy_after < entropy_before || cell.is_contradiction() {
+                let previous = std::mem::replace(&mut space[propagating], cell);
+                let was_resolved = is_resolved(&previous);
+                if let Some(journal) = journal.as_deref_mut() {
+                    journal.push((propagating, previous));
+                }
+                if space[propagating].is_contradiction() {
+                    if was_resolved {
+                        self.resolved -= 1;
+                    }
+                    return Err(propagating);
+                }
+                if is_resolved(&space[propagating]) {
+                    self.resolved += 1;
+                } else {
+                    self.queue.push(propagating, entropy_after);
+                }
+                for neighbor in self.neighbors.iter().flatten() {
+                    if space[*neighbor].entropy() != 0 || *neighbor == propagating {
+                        self.to_propagate.push_back(*neighbor);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether a state counts towards [Progress::resolved]. Contradictions have
+// zero entropy too, but are never resolved.
+fn is_resolved<St: State>(state: &St) -> bool {
+    state.entropy() == 0 && !state.is_contradiction()
+}
@@ -17,10 +17,10 @@ pub trait InvertDelta {
 /// In order to support arbitrary dimension and shape, two associated types are
 /// defined:
 /// - `Coordinate` is the index type for this space. Cells in the space are
-/// uniquely identified by coordinates.
+///   uniquely identified by coordinates.
 /// - `CoordinateDelta` represents adjacency relations between cells. In
-/// general, a collapse rule supplies a list of coordinate deltas to get
-/// neighbor cell coordinates.
+///   general, a collapse rule supplies a list of coordinate deltas to get
+///   neighbor cell coordinates.
 pub trait Space<T>: IndexMut<Self::Coordinate, Output = T> + 'static {
     /// Coordinates for cells in the space
     type Coordinate: Copy + Hash + Ord;
@@ -34,8 +34,9 @@ pub trait Space<T>: IndexMut<Self::Coordinate, Output = T> + 'static {
     /// * `coord` - Coordinate of the cell to find neighbors for
     /// * `neighbor_directions` - List of neighbor cell offsets
     /// * `neighbors` - Output list of neighbor coordinates. Must be at least
-    /// as long as neighbor_directions. Set to `None` for neighbors which are
-    /// out of bounds for the space.
+    ///   as long as neighbor_directions. Set to `None` for neighbors which are
+    ///   out of bounds for the space, which rules treat as the cell facing the
+    ///   outside of the space in that direction.
     fn neighbors(
         &self,
         coord: Self::Coordinate,
@@ -0,0 +1,115 @@
+use bevy_utils::HashMap;
+use std::ops::{Index, IndexMut};
+
+use crate::Space;
+
+/// A 3D grid which only contains the cells inserted into it, for collapsing
+/// arbitrary voxel masks rather than whole boxes.
+///
+/// Uses the same y-up coordinates and deltas as [crate::cube_grid::CubeGrid].
+/// Neighbors which aren't in the grid are out of bounds, so boundary
+/// constraints of rules apply along the surface of the mask.
+///
+/// * `T` - The state of each cell
+#[derive(Clone, Debug)]
+pub struct SparseGrid<T> {
+    cells: HashMap<(isize, isize, isize), T>,
+}
+
+impl<T> SparseGrid<T> {
+    /// Creates a new SparseGrid without any cells
+    pub fn new() -> Self {
+        Self {
+            cells: HashMap::default(),
+        }
+    }
+
+    /// Inserts a cell at `coord` with the initial state `state`, returning
+    /// the previous state if the cell was already in the grid
+    pub fn insert(&mut self, coord: (isize, isize, isize), state: T) -> Option<T> {
+        self.cells.insert(coord, state)
+    }
+
+    /// Removes the cell at `coord` from the grid, returning its state
+    pub fn remove(&mut self, coord: (isize, isize, isize)) -> Option<T> {
+        self.cells.remove(&coord)
+    }
+
+    /// Whether the grid contains a cell at `coord`
+    pub fn contains(&self, coord: (isize, isize, isize)) -> bool {
+        self.cells.contains_key(&coord)
+    }
+
+    /// Gets the state of the cell at `coord`, or `None` if it isn't in the
+    /// grid
+    pub fn get(&self, coord: (isize, isize, isize)) -> Option<&T> {
+        self.cells.get(&coord)
+    }
+
+    /// The number of cells in the grid
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<((isize, isize, isize), T)> for SparseGrid<T> {
+    fn from_iter<I: IntoIterator<Item = ((isize, isize, isize), T)>>(iter: I) -> Self {
+        Self {
+            cells: iter.into_iter().collect(),
+        }
+    }
+}
+
+// Access to a certain cells possible states
+impl<T: 'static> Index<(isize, isize, isize)> for SparseGrid<T> {
+    type Output = T;
+
+    fn index(&self, index: (isize, isize, isize)) -> &Self::Output {
+        self.cells.get(&index).expect("coordinate not in grid")
+    }
+}
+
+// Mutable access to a certain cells possible states
+impl<T: 'static> IndexMut<(isize, isize, isize)> for SparseGrid<T> {
+    fn index_mut(&mut self, index: (isize, isize, isize)) -> &mut Self::Output {
+        self.cells.get_mut(&index).expect("coordinate not in grid")
+    }
+}
+
+impl<T: 'static> Space<T> for SparseGrid<T> {
+    type Coordinate = (isize, isize, isize);
+    type CoordinateDelta = (isize, isize, isize);
+
+    fn coordinate_list(&self) -> Box<[Self::Coordinate]> {
+        // Sorted so that collapsing is deterministic for a given rng, whatever
+        // order cells were inserted in
+        let mut coords = self.cells.keys().copied().collect::<Vec<_>>();
+        coords.sort_unstable();
+        coords.into_boxed_slice()
+    }
+
+    fn neighbors(
+        &self,
+        coord: Self::Coordinate,
+        neighbor_directions: &[Self::CoordinateDelta],
+        neighbors: &mut [Option<Self::Coordinate>],
+    ) {
+        assert!(neighbor_directions.len() <= neighbors.len());
+
+        let (x, y, z) = coord;
+        for (neighbor, (dx, dy, dz)) in neighbors.iter_mut().zip(neighbor_directions) {
+            let neighbor_coord = (x + dx, y + dy, z + dz);
+            *neighbor = self.contains(neighbor_coord).then_some(neighbor_coord);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{cube_grid::wrap_axis, InvertDelta, Space};
+use std::ops::{Index, IndexMut};
+
+/*
+Uses a y-down coordinate system, as with images
+
+(0,0) - - - - (width,0)
+      |       |
+      |       |
+(0,height) - - (width,height)
+*/
+
+#[derive(Clone, Debug)]
+pub struct SquareGrid<T> {
+    cells: Box<[T]>,
+    width: isize,
+    height: isize,
+    // Whether neighbors wrap around the x and y axes
+    wrap: (bool, bool),
+}
+
+impl InvertDelta for (isize, isize) {
+    fn invert_delta(&self) -> Self {
+        let (dx, dy) = *self;
+        (-dx, -dy)
+    }
+}
+
+impl<T> SquareGrid<T> {
+    // width - x axis
+    // height - y axis
+    // init_fn - callback to set the initial state of each cell based on coordinate
+    pub fn new(width: isize, height: isize, init_fn: impl Fn(isize, isize) -> T) -> Self {
+        let mut cells = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(init_fn(x, y));
+            }
+        }
+        Self {
+            cells: cells.into_boxed_slice(),
+            width,
+            height,
+            wrap: (false, false),
+        }
+    }
+
+    // Makes neighbors wrap around the grid on each axis that's set, as with
+    // `CubeGrid::with_wrap`
+    pub fn with_wrap(mut self, wrap_x: bool, wrap_y: bool) -> Self {
+        self.wrap = (wrap_x, wrap_y);
+        self
+    }
+}
+
+// Access to a certain cells possible states
+impl<T: 'static> Index<<SquareGrid<T> as Space<T>>::Coordinate> for SquareGrid<T> {
+    type Output = T;
+
+    fn index(&self, index: <SquareGrid<T> as Space<T>>::Coordinate) -> &Self::Output {
+        let (x, y) = index;
+        // Cells are stored row by row, as initialized in new()
+        &self.cells[(y * self.width + x) as usize]
+    }
+}
+
+// Mutable access to a certain cells possible states
+impl<T: 'static> IndexMut<<SquareGrid<T> as Space<T>>::Coordinate> for SquareGrid<T> {
+    fn index_mut(&mut self, index: <SquareGrid<T> as Space<T>>::Coordinate) -> &mut Self::Output {
+        let (x, y) = index;
+        &mut self.cells[(y * self.width + x) as usize]
+    }
+}
+
+impl<T: 'static> Space<T> for SquareGrid<T> {
+    type Coordinate = (isize, isize);
+    type CoordinateDelta = (isize, isize);
+
+    fn coordinate_list(&self) -> Box<[Self::Coordinate]> {
+        let mut coords = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                coords.push((x, y));
+            }
+        }
+        coords.into_boxed_slice()
+    }
+
+    fn neighbors(
+        &self,
+        coord: Self::Coordinate,
+        neighbor_directions: &[Self::CoordinateDelta],
+        neighbors: &mut [Option<Self::Coordinate>],
+    ) {
+        assert!(neighbor_directions.len() <= neighbors.len());
+
+        // Neighbors past the edge of the grid are None unless the axis wraps
+        let (x, y) = coord;
+        let (wrap_x, wrap_y) = self.wrap;
+        for (neighbor, (dx, dy)) in neighbors.iter_mut().zip(neighbor_directions) {
+            *neighbor = match (
+                wrap_axis(x + dx, self.width, wrap_x),
+                wrap_axis(y + dy, self.height, wrap_y),
+            ) {
+                (Some(nx), Some(ny)) => Some((nx, ny)),
+                _ => None,
+            };
+        }
+    }
+}
@@ -4,4 +4,11 @@ pub trait State: Clone + PartialEq {
     /// final, and cannot be collapsed further, while higher values mean there
     /// are more possible values this state could collapse to.
     fn entropy(&self) -> u32;
+
+    /// Checks if this state has no possible values left. Such a state also has
+    /// zero entropy, but unlike a final state it can never be satisfied.
+    ///
+    /// Solvers rely on this to tell contradictions apart from final states,
+    /// so state types which cannot become contradictory should return `false`.
+    fn is_contradiction(&self) -> bool;
 }
@@ -0,0 +1,323 @@
+use bevy_utils::HashMap;
+use rand::RngCore;
+
+use crate::{
+    observe_cell, queue_unresolved,
+    set_rule::{SetCollapseObserver, SetCollapseRule},
+    CollapseError, CollapseRule, EntropyQueue, Final, SetState, Space, State,
+};
+
+/// Perform the wave function collapse algorithm like [crate::collapse], using
+/// AC-4 style support counting to propagate a [SetCollapseRule].
+///
+/// For every cell, neighbor direction and tile, the solver counts how many
+/// tiles of that neighbor allow the tile. When a tile is removed from a cell,
+/// only the counts it contributed to are decremented, and a tile is removed
+/// once any of its counts reaches zero. Unlike [crate::collapse], cells are
+/// never re-checked against every rule when a neighbor changes, which makes
+/// this much faster on large spaces at the cost of memory for the counts.
+///
+/// The results follow the same rules as [crate::collapse]. States must be
+/// final states identified by tile index through [Final], such as
+/// [crate::bitset_state::BitsetState].
+pub fn collapse_with_support_counting<
+    S: State + SetState + Final<usize>,
+    Sp: Space<S>,
+    O: SetCollapseObserver<S>,
+    R: RngCore,
+>(
+    space: &mut Sp,
+    rule: &SetCollapseRule<S, Sp, O>,
+    rng: &mut R,
+) -> Result<(), CollapseError<Sp::Coordinate>>
+where
+    Sp::CoordinateDelta: Clone,
+{
+    let mut queue = EntropyQueue::new();
+    queue.reset(rng);
+    let neighbor_directions = rule.neighbor_offsets();
+    let mut neighbors = vec![None; neighbor_directions.len()].into_boxed_slice();
+    let mut supports = Supports::new(space, rule, &neighbor_directions)
+        .and_then(|mut supports| supports.propagate(space, &mut queue).map(|_| supports))
+        .map_err(|coordinate| CollapseError::Contradiction {
+            coordinate,
+            last_observed: None,
+        })?;
+    queue_unresolved(space, &supports.coordinates, &mut queue);
+
+    while let Some(to_collapse) = queue.pop(space) {
+        let contradiction = |coordinate| CollapseError::Contradiction {
+            coordinate,
+            last_observed: Some(to_collapse),
+        };
+        let previous = observe_cell(
+            space,
+            rule,
+            to_collapse,
+            &neighbor_directions,
+            &mut neighbors,
+            rng,
+        );
+        if space[to_collapse].is_contradiction() {
+            return Err(contradiction(to_collapse));
+        }
+        supports.observed(space, to_collapse, &previous);
+        supports
+            .propagate(space, &mut queue)
+            .map_err(contradiction)?;
+    }
+    Ok(())
+}
+
+// Support counts for every tile of every cell, along with the adjacency tables
+// needed to update them. Cells and tiles are referred to by dense indices.
+struct Supports<S, C> {
+    coordinates: Box<[C]>,
+    cell_indices: HashMap<C, usize>,
+    directions: usize,
+    tiles: usize,
+    // States holding just the tile at each index, if any rule mentions it
+    tile_states: Box<[Option<S>]>,
+    // For each direction and tile, the tiles which allow it as their neighbor
+    // in that direction, indexed by `direction * tiles + tile`
+    supported: Box<[Vec<usize>]>,
+    // The cells which have each cell as a neighbor, along with the direction
+    // it lies in from them. Entries for cell `i` are in
+    // `dependents[dependents_start[i]..dependents_start[i + 1]]`.
+    dependents_start: Box<[usize]>,
+    dependents: Box<[(usize, usize)]>,
+    // Number of neighbor tiles allowing each tile of each cell, indexed by
+    // `(cell * directions + direction) * tiles + tile`. Counts for directions
+    // without a neighbor are 1, or 0 for tiles their boundary disallows, and
+    // are never decremented.
+    counts: Box<[u32]>,
+    // Tiles removed from cells whose removal hasn't been propagated yet
+    removed: Vec<(usize, usize)>,
+    // Cells changed since the last propagation finished
+    changed: Vec<usize>,
+}
+
+impl<S: State + SetState + Final<usize>, C: Copy + Eq + std::hash::Hash + Ord> Supports<S, C> {
+    // Builds the tables and counts for the initial state of `space`, and
+    // queues the removal of every tile which is unsupported from the start.
+    fn new<Sp: Space<S, Coordinate = C>, O: SetCollapseObserver<S>>(
+        space: &mut Sp,
+        rule: &SetCollapseRule<S, Sp, O>,
+        neighbor_directions: &[Sp::CoordinateDelta],
+    ) -> Result<Self, C> {
+        let directions = neighbor_directions.len();
+        let rule_tiles = rule.tiles();
+        let tiles = rule_tiles
+            .iter()
+            .filter_map(|state| state.get())
+            .max()
+            .map_or(0, |tile| tile + 1);
+
+        let mut tile_states = vec![None; tiles].into_boxed_slice();
+        let mut supported = vec![Vec::new(); directions * tiles].into_boxed_slice();
+        for (rule_tile, state) in rule_tiles.iter().enumerate() {
+            let Some(neighbor_tile) = state.get() else {
+                continue;
+            };
+            tile_states[neighbor_tile] = Some(state.clone());
+            for direction in 0..directions {
+                let allowed_by = rule.allowed_by(direction, rule_tile);
+                for tile in tiles_of(allowed_by) {
+                    if tile < tiles {
+                        supported[direction * tiles + neighbor_tile].push(tile);
+                    }
+                }
+            }
+        }
+
+        let coordinates = space.coordinate_list();
+        let cell_indices = coordinates
+            .iter()
+            .enumerate()
+            .map(|(index, coord)| (*coord, index))
+            .collect::<HashMap<_, _>>();
+        let mut neighbor_cells = vec![None; coordinates.len() * directions];
+        let mut neighbors = vec![None; directions];
+        for (cell, coord) in coordinates.iter().enumerate() {
+            space.neighbors(*coord, neighbor_directions, &mut neighbors);
+            for (direction, neighbor) in neighbors.iter().enumerate() {
+                neighbor_cells[cell * directions + direction] =
+                    neighbor.and_then(|neighbor| cell_indices.get(&neighbor).copied());
+            }
+        }
+
+        let mut dependents_start = vec![0; coordinates.len() + 1].into_boxed_slice();
+        for neighbor in neighbor_cells.iter().flatten() {
+            dependents_start[*neighbor + 1] += 1;
+        }
+        for i in 1..dependents_start.len() {
+            dependents_start[i] += dependents_start[i - 1];
+        }
+        let mut filled = dependents_start.clone();
+        let mut dependents = vec![(0, 0); dependents_start[coordinates.len()]].into_boxed_slice();
+        let mut counts = vec![0; coordinates.len() * directions * tiles].into_boxed_slice();
+        // Most neighbors start out in the same state, so counts are copied from
+        // the last neighbor in the same direction when their states match
+        let mut last_counted = vec![None; directions];
+        for (i, neighbor) in neighbor_cells.iter().enumerate() {
+            let (cell, direction) = (i / directions, i % directions);
+            let Some(neighbor) = *neighbor else {
+                // Tiles which can't face the outside start out unsupported
+                let boundary = rule.boundary_disallowed(direction);
+                for tile in 0..tiles {
+                    let disallowed =
+                        boundary.is_some_and(|boundary| has_tile(&tile_states, boundary, tile));
+                    counts[i * tiles + tile] = u32::from(!disallowed);
+                }
+                continue;
+            };
+            dependents[filled[neighbor]] = (cell, direction);
+            filled[neighbor] += 1;
+            let neighbor_state = &space[coordinates[neighbor]];
+            match &last_counted[direction] {
+                Some((state, counted)) if state == neighbor_state => {
+                    counts.copy_within(counted * tiles..(counted + 1) * tiles, i * tiles);
+                }
+                _ => {
+                    for neighbor_tile in tiles_in(&tile_states, neighbor_state) {
+                        for tile in &supported[direction * tiles + neighbor_tile] {
+                            counts[i * tiles + *tile] += 1;
+                        }
+                    }
+                    last_counted[direction] = Some((neighbor_state.clone(), i));
+                }
+            }
+        }
+
+        let mut supports = Self {
+            coordinates,
+            cell_indices,
+            directions,
+            tiles,
+            tile_states,
+            supported,
+            dependents_start,
+            dependents,
+            counts,
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        for cell in 0..supports.coordinates.len() {
+            let coord = supports.coordinates[cell];
+            if space[coord].is_contradiction() {
+                return Err(coord);
+            }
+            for tile in 0..tiles {
+                if (0..directions).any(|d| supports.count(cell, d, tile) == 0) {
+                    supports.remove(space, cell, tile)?;
+                }
+            }
+        }
+        Ok(supports)
+    }
+
+    fn count(&self, cell: usize, direction: usize, tile: usize) -> u32 {
+        self.counts[(cell * self.directions + direction) * self.tiles + tile]
+    }
+
+    // Records the tiles removed from `coord` by observing it
+    fn observed<Sp: Space<S, Coordinate = C>>(&mut self, space: &Sp, coord: C, previous: &S) {
+        let cell = self.cell_indices[&coord];
+        for tile in 0..self.tiles {
+            if has_tile(&self.tile_states, previous, tile)
+                && !has_tile(&self.tile_states, &space[coord], tile)
+            {
+                self.removed_tile(cell, tile);
+            }
+        }
+    }
+
+    // Removes `tile` from the cell, returning its coordinate if that leaves it
+    // without any states
+    fn remove<Sp: Space<S, Coordinate = C>>(
+        &mut self,
+        space: &mut Sp,
+        cell: usize,
+        tile: usize,
+    ) -> Result<(), C> {
+        let coord = self.coordinates[cell];
+        let Some(tile_state) = &self.tile_states[tile] else {
+            return Ok(());
+        };
+        if !space[coord].has_any_of(tile_state) {
+            return Ok(());
+        }
+        space[coord].clear_states(tile_state);
+        self.removed_tile(cell, tile);
+        if space[coord].is_contradiction() {
+            return Err(coord);
+        }
+        Ok(())
+    }
+
+    // Zeroes the counts of a removed tile, so that it's never removed again,
+    // and queues the removal for propagation
+    fn removed_tile(&mut self, cell: usize, tile: usize) {
+        for direction in 0..self.directions {
+            self.counts[(cell * self.directions + direction) * self.tiles + tile] = 0;
+        }
+        self.removed.push((cell, tile));
+        self.changed.push(cell);
+    }
+
+    // Propagates every queued removal, queueing changed cells for observation
+    fn propagate<Sp: Space<S, Coordinate = C>>(
+        &mut self,
+        space: &mut Sp,
+        queue: &mut EntropyQueue<C>,
+    ) -> Result<(), C> {
+        while let Some((cell, removed_tile)) = self.removed.pop() {
+            for i in self.dependents_start[cell]..self.dependents_start[cell + 1] {
+                let (dependent, direction) = self.dependents[i];
+                let supported = direction * self.tiles + removed_tile;
+                for j in 0..self.supported[supported].len() {
+                    let tile = self.supported[supported][j];
+                    let count = &mut self.counts
+                        [(dependent * self.directions + direction) * self.tiles + tile];
+                    if *count > 0 {
+                        *count -= 1;
+                        if *count == 0 {
+                            self.remove(space, dependent, tile)?;
+                        }
+                    }
+                }
+            }
+        }
+        self.changed.sort_unstable();
+        self.changed.dedup();
+        for cell in self.changed.drain(..) {
+            let coord = self.coordinates[cell];
+            let entropy = space[coord].entropy();
+            if entropy > 0 {
+                queue.push(coord, entropy);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn has_tile<S: SetState>(tile_states: &[Option<S>], state: &S, tile: usize) -> bool {
+    tile_states[tile]
+        .as_ref()
+        .is_some_and(|tile_state| state.has_any_of(tile_state))
+}
+
+// The index of every tile of `state` which has a rule
+fn tiles_in<'a, S: SetState>(
+    tile_states: &'a [Option<S>],
+    state: &'a S,
+) -> impl Iterator<Item = usize> + 'a {
+    (0..tile_states.len()).filter(move |tile| has_tile(tile_states, state, *tile))
+}
+
+// The index of every tile in a state
+fn tiles_of<S: SetState + Final<usize>>(state: &S) -> impl Iterator<Item = usize> {
+    let mut final_states = Vec::new();
+    state.collect_final_states(&mut final_states);
+    final_states.into_iter().filter_map(|state| state.get())
+}
@@ -0,0 +1,127 @@
+use bevy_utils::HashMap;
+use std::hash::Hash;
+
+use crate::{Final, Space};
+
+/// Registry which interns user facing tile keys into dense indices.
+///
+/// Rules and spaces can then use compact index-based states such as
+/// [crate::bitset_state::DynBitsetState], while tile names, enums or other
+/// keys are only used when building rules and reading back results.
+///
+/// * `K` - The user facing tile key
+#[derive(Clone, Debug)]
+pub struct TileSet<K: Hash + Eq + Clone> {
+    keys: Vec<K>,
+    indices: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq + Clone> TileSet<K> {
+    /// Creates a new TileSet without any tiles
+    pub fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            indices: HashMap::default(),
+        }
+    }
+
+    /// Adds `key` to the set if it isn't already, and returns its index.
+    /// Indices are handed out in insertion order starting from zero.
+    pub fn insert(&mut self, key: K) -> usize {
+        if let Some(index) = self.indices.get(&key) {
+            return *index;
+        }
+        let index = self.keys.len();
+        self.indices.insert(key.clone(), index);
+        self.keys.push(key);
+        index
+    }
+
+    /// Gets the index of `key`, or `None` if it was never inserted
+    pub fn index(&self, key: &K) -> Option<usize> {
+        self.indices.get(key).copied()
+    }
+
+    /// Gets the key with index `index`
+    pub fn key(&self, index: usize) -> Option<&K> {
+        self.keys.get(index)
+    }
+
+    /// Every key in the set, in index order
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// The number of tiles in the set
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Creates a state holding every tile in the set, for use as the
+    /// `all_state` of a rule and the initial state of cells.
+    pub fn all_state<S: FromIterator<usize>>(&self) -> S {
+        (0..self.keys.len()).collect()
+    }
+
+    /// Creates a state holding the tiles in `keys`, or `None` if any of them
+    /// isn't in the set.
+    pub fn state<S: FromIterator<usize>>(&self, keys: &[K]) -> Option<S> {
+        keys.iter().map(|key| self.index(key)).collect()
+    }
+
+    /// Creates a state holding only the tile `key`, or `None` if it isn't in
+    /// the set.
+    pub fn final_state<S: FromIterator<usize>>(&self, key: &K) -> Option<S> {
+        self.index(key)
+            .map(|index| std::iter::once(index).collect())
+    }
+
+    /// Gets the key of the tile a state has collapsed to, or `None` if the
+    /// state isn't final.
+    pub fn resolve<S: Final<usize>>(&self, state: &S) -> Option<&K> {
+        state.get().and_then(|index| self.key(index))
+    }
+
+    /// Resolves every cell of a collapsed space as with [TileSet::resolve],
+    /// in the order given by [Space::coordinate_list].
+    pub fn resolve_space<S: Final<usize>, Sp: Space<S>>(
+        &self,
+        space: &Sp,
+    ) -> Vec<(Sp::Coordinate, Option<&K>)> {
+        space
+            .coordinate_list()
+            .iter()
+            .map(|coord| (*coord, self.resolve(&space[*coord])))
+            .collect()
+    }
+
+    /// Builds the weights for a [crate::set_rule::WeightedSetCollapseObserver]
+    /// from a weight for each key.
+    pub fn weights(&self, weight_fn: impl Fn(&K) -> u32) -> HashMap<usize, u32> {
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (index, weight_fn(key)))
+            .collect()
+    }
+}
+
+impl<K: Hash + Eq + Clone> Default for TileSet<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone> FromIterator<K> for TileSet<K> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        let mut tiles = Self::new();
+        for key in iter {
+            tiles.insert(key);
+        }
+        tiles
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::{self, Debug, Display};
+
+use crate::{CollapseRule, Neighbors, Space, State};
+
+/// A way in which a cell of a space breaks a rule, as found by [verify]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Violation<C> {
+    /// The cell isn't final yet
+    Unresolved { coordinate: C },
+    /// The cell has no possible states
+    Contradiction { coordinate: C },
+    /// The cell isn't allowed next to one of its neighbors.
+    ///
+    /// * `coordinate` - The cell breaking the rule
+    /// * `direction` - Index of the neighbor in
+    ///   [CollapseRule::neighbor_offsets]
+    /// * `neighbor` - The neighbor's coordinate, or `None` if it's out of
+    ///   bounds for the space
+    Neighbor {
+        coordinate: C,
+        direction: usize,
+        neighbor: Option<C>,
+    },
+}
+
+impl<C: Debug> Display for Violation<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unresolved { coordinate } => write!(f, "{:?} isn't final", coordinate),
+            Self::Contradiction { coordinate } => {
+                write!(f, "{:?} has no possible states", coordinate)
+            }
+            Self::Neighbor {
+                coordinate,
+                direction,
+                neighbor: Some(neighbor),
+            } => write!(
+                f,
+                "{:?} isn't allowed next to {:?} in direction {}",
+                coordinate, neighbor, direction
+            ),
+            Self::Neighbor {
+                coordinate,
+                direction,
+                neighbor: None,
+            } => write!(
+                f,
+                "{:?} isn't allowed at the edge of the space in direction {}",
+                coordinate, direction
+            ),
+        }
+    }
+}
+
+/// Checks that every cell of a collapsed space is final, has a state, and is
+/// allowed next to each of its neighbors by `rule`.
+///
+/// Returns every violation found, in the order given by
+/// [Space::coordinate_list], or an empty list if the space is a valid
+/// solution. Neighbors are only checked for final cells, and a conflict
+/// between two cells is reported from both sides when the rule rejects both.
+pub fn verify<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>>(
+    space: &Sp,
+    rule: &Rule,
+) -> Vec<Violation<Sp::Coordinate>> {
+    let neighbor_directions = rule.neighbor_offsets();
+    let mut neighbors = vec![None; neighbor_directions.len()].into_boxed_slice();
+    let mut conflicts = Vec::new();
+    let mut violations = Vec::new();
+    for coordinate in space.coordinate_list().iter().copied() {
+        let cell = &space[coordinate];
+        if cell.is_contradiction() {
+            violations.push(Violation::Contradiction { coordinate });
+            continue;
+        }
+        if cell.entropy() > 0 {
+            violations.push(Violation::Unresolved { coordinate });
+            continue;
+        }
+        space.neighbors(coordinate, &neighbor_directions, &mut neighbors);
+        conflicts.clear();
+        rule.conflicts(cell, &Neighbors::new(space, &neighbors), &mut conflicts);
+        violations.extend(conflicts.iter().map(|direction| Violation::Neighbor {
+            coordinate,
+            direction: *direction,
+            neighbor: neighbors[*direction],
+        }));
+    }
+    violations
+}
@@ -0,0 +1,63 @@
+// Rules and helpers shared by the integration tests. Not every test uses all
+// of them.
+#![allow(dead_code)]
+
+use wfc3d::bitset_state::BitsetState;
+use wfc3d::set_rule::*;
+use wfc3d::square_grid::SquareGrid;
+
+pub type State = BitsetState<1>;
+pub type Grid = SquareGrid<State>;
+
+pub const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+pub fn state(tiles: &[usize]) -> State {
+    tiles.iter().copied().collect()
+}
+
+// Tiles 0 and 1 alternate like a checkerboard
+pub fn checkerboard() -> SetCollapseRule<State, Grid, UniformSetCollapseObserver> {
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, state(&[0, 1]));
+    for direction in DIRECTIONS {
+        builder = builder
+            .allow(&state(&[0]), &[(direction, state(&[1]))])
+            .allow(&state(&[1]), &[(direction, state(&[0]))]);
+    }
+    builder.build()
+}
+
+// Tiles 0 and 1 can be next to anything, so nothing is ever propagated
+pub fn unconstrained() -> SetCollapseRule<State, Grid, UniformSetCollapseObserver> {
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, state(&[0, 1]));
+    for direction in DIRECTIONS {
+        builder = builder.allow(&state(&[0, 1]), &[(direction, state(&[0, 1]))]);
+    }
+    builder.build()
+}
+
+// Tiles 0 to 3, with the tiles allowed to the right of and below each tile.
+// Collapsing this often runs into contradictions.
+pub const RIGHT: [&[usize]; 4] = [&[0, 1, 2, 3], &[0, 1], &[0, 1, 3], &[2]];
+pub const BELOW: [&[usize]; 4] = [&[0, 3], &[0, 3], &[2, 3], &[1, 3]];
+
+pub fn contradictory() -> SetCollapseRule<State, Grid, UniformSetCollapseObserver> {
+    contradictory_builder().build()
+}
+
+pub fn contradictory_builder() -> SetCollapseRuleBuilder<State, Grid, UniformSetCollapseObserver> {
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, state(&[0, 1, 2, 3]));
+    for tile in 0..4 {
+        let left = (0..4).filter(|other| RIGHT[*other].contains(&tile));
+        let above = (0..4).filter(|other| BELOW[*other].contains(&tile));
+        builder = builder.allow(
+            &state(&[tile]),
+            &[
+                ((1, 0), state(RIGHT[tile])),
+                ((0, 1), state(BELOW[tile])),
+                ((-1, 0), left.collect()),
+                ((0, -1), above.collect()),
+            ],
+        );
+    }
+    builder
+}
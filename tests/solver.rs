@@ -0,0 +1,126 @@
+use rand::{rngs::StdRng, SeedableRng};
+use std::sync::atomic::AtomicBool;
+use wfc3d::{collapse, verify, CollapseStatus, Limits, Solver};
+
+mod common;
+use common::*;
+
+#[test]
+fn reused_solver_reads_new_shape() {
+    let rule = checkerboard();
+    let mut solver = Solver::new(&rule);
+    let mut rng = StdRng::seed_from_u64(0);
+    for size in [2, 4, 3] {
+        let mut grid = Grid::new(size, size, |_, _| state(&[0, 1]));
+        solver.collapse(&mut grid, &mut rng).unwrap();
+        assert!(verify(&grid, &rule).is_empty());
+        assert_eq!(solver.progress(&grid).total, (size * size) as usize);
+    }
+}
+
+#[test]
+fn reused_solver_reads_new_orientation() {
+    let rule = checkerboard();
+    let mut solver = Solver::new(&rule);
+    for seed in 0..5 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for (width, height) in [(6, 1), (1, 6)] {
+            let mut grid = Grid::new(width, height, |_, _| state(&[0, 1]));
+            solver.collapse(&mut grid, &mut rng).unwrap();
+            assert!(verify(&grid, &rule).is_empty());
+        }
+    }
+}
+
+#[test]
+fn collapse_within_starts_new_run() {
+    let rule = unconstrained();
+    let mut solver = Solver::new(&rule);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut stepped = Grid::new(2, 2, |_, _| state(&[0, 1]));
+    // Propagates the initial states, observes a cell and propagates that
+    for _ in 0..3 {
+        solver.step(&mut stepped, &mut rng);
+    }
+
+    let mut grid = Grid::new(4, 4, |_, _| state(&[0, 1]));
+    let status = solver.collapse_within(&mut grid, Limits::none(), &mut rng);
+    assert_eq!(status, Ok(CollapseStatus::Finished));
+    assert!(verify(&grid, &rule).is_empty());
+}
+
+#[test]
+fn resume_within_continues_interrupted_run() {
+    let rule = checkerboard();
+    let mut solver = Solver::new(&rule);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut grid = Grid::new(4, 4, |_, _| state(&[0, 1]));
+    let cancel = AtomicBool::new(true);
+    let status = solver.collapse_within(&mut grid, Limits::none().with_cancel(&cancel), &mut rng);
+    assert_eq!(status, Ok(CollapseStatus::Interrupted));
+
+    let status = solver.resume_within(&mut grid, Limits::none(), &mut rng);
+    assert_eq!(status, Ok(CollapseStatus::Finished));
+    assert!(verify(&grid, &rule).is_empty());
+}
+
+#[test]
+fn backtracking_resolves_every_cell() {
+    let rule = contradictory();
+    let mut solver = Solver::new(&rule);
+    let mut backtracked = 0;
+    for seed in 0..20 {
+        let mut grid = Grid::new(8, 8, |_, _| state(&[0, 1, 2, 3]));
+        let mut rng = StdRng::seed_from_u64(seed);
+        if collapse(&mut grid, &rule, &mut rng).is_ok() {
+            continue;
+        }
+        // The same seed runs into a contradiction, so backtracking is needed
+        let mut grid = Grid::new(8, 8, |_, _| state(&[0, 1, 2, 3]));
+        let mut rng = StdRng::seed_from_u64(seed);
+        solver
+            .collapse_with_backtracking(&mut grid, 64, &mut rng)
+            .unwrap();
+        assert!(verify(&grid, &rule).is_empty());
+        let progress = solver.progress(&grid);
+        assert_eq!(progress.resolved, progress.total);
+        assert_eq!(progress.min_entropy, None);
+        backtracked += 1;
+    }
+    assert!(backtracked > 0);
+}
+
+#[test]
+fn initial_propagation_can_be_interrupted() {
+    // Fixing one corner resolves the whole checkerboard while propagating the
+    // initial states
+    let initial = |x, y| {
+        if (x, y) == (0, 0) {
+            state(&[0])
+        } else {
+            state(&[0, 1])
+        }
+    };
+    let rule = checkerboard();
+    let mut solver = Solver::new(&rule);
+    let mut grid = Grid::new(32, 32, initial);
+    let mut rng = StdRng::seed_from_u64(0);
+    let cancel = AtomicBool::new(true);
+    let status = solver.collapse_within(&mut grid, Limits::none().with_cancel(&cancel), &mut rng);
+    assert_eq!(status, Ok(CollapseStatus::Interrupted));
+    let progress = solver.progress(&grid);
+    assert!(progress.resolved < progress.total / 2);
+
+    let status = solver.resume_within(&mut grid, Limits::none(), &mut rng);
+    assert_eq!(status, Ok(CollapseStatus::Finished));
+    let progress = solver.progress(&grid);
+    assert_eq!(progress.resolved, progress.total);
+
+    let mut collapsed = Grid::new(32, 32, initial);
+    collapse(&mut collapsed, &rule, &mut StdRng::seed_from_u64(0)).unwrap();
+    for y in 0..32 {
+        for x in 0..32 {
+            assert_eq!(grid[(x, y)], collapsed[(x, y)]);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use rand::{rngs::StdRng, SeedableRng};
+use wfc3d::set_rule::{SetCollapseRule, UniformSetCollapseObserver};
+use wfc3d::{collapse, collapse_with_support_counting, verify, CollapseError};
+
+mod common;
+use common::*;
+
+type Rule = SetCollapseRule<State, Grid, UniformSetCollapseObserver>;
+
+// Collapses a grid with both [collapse] and support counting from each seed,
+// checking every successful result with `verify` and `check`. Returns how many
+// runs of each succeeded.
+fn collapse_both(rule: &Rule, grid: impl Fn() -> Grid, check: impl Fn(&Grid)) -> (usize, usize) {
+    let mut succeeded = (0, 0);
+    for seed in 0..50 {
+        let mut collapsed = grid();
+        if collapse(&mut collapsed, rule, &mut StdRng::seed_from_u64(seed)).is_ok() {
+            assert!(verify(&collapsed, rule).is_empty());
+            check(&collapsed);
+            succeeded.0 += 1;
+        }
+        let mut counted = grid();
+        let mut rng = StdRng::seed_from_u64(seed);
+        if collapse_with_support_counting(&mut counted, rule, &mut rng).is_ok() {
+            assert!(verify(&counted, rule).is_empty());
+            check(&counted);
+            succeeded.1 += 1;
+        }
+    }
+    succeeded
+}
+
+fn all_tiles(_: isize, _: isize) -> State {
+    state(&[0, 1, 2, 3])
+}
+
+#[test]
+fn results_are_valid() {
+    let rule = contradictory();
+    let (collapsed, counted) = collapse_both(&rule, || Grid::new(12, 12, all_tiles), |_| {});
+    assert!(collapsed > 0 && counted > 0);
+}
+
+#[test]
+fn boundaries_are_respected() {
+    // Only tile 2 can face the outside on the right
+    let rule = contradictory_builder()
+        .boundary((1, 0), &state(&[2]))
+        .build();
+    let (collapsed, counted) = collapse_both(
+        &rule,
+        || Grid::new(8, 8, all_tiles),
+        |grid| {
+            for y in 0..8 {
+                assert_eq!(grid[(7, y)], state(&[2]));
+            }
+        },
+    );
+    assert!(collapsed > 0 && counted > 0);
+}
+
+#[test]
+fn restricted_initial_states_are_kept() {
+    // Rows of identical restricted cells, so most neighbor counts are copied
+    let initial = |x: isize, y: isize| match (x, y) {
+        (_, 0) => state(&[0]),
+        (_, 1) => state(&[0, 3]),
+        (x, _) if x % 3 == 0 => state(&[0, 1]),
+        _ => all_tiles(x, y),
+    };
+    let rule = contradictory();
+    let (collapsed, counted) = collapse_both(
+        &rule,
+        || Grid::new(8, 8, initial),
+        |grid| {
+            for y in 0..8 {
+                for x in 0..8 {
+                    let tile = grid[(x, y)].iter().next().unwrap();
+                    assert!(initial(x, y).contains(tile));
+                }
+            }
+        },
+    );
+    assert!(collapsed > 0 && counted > 0);
+}
+
+#[test]
+fn wrapped_cells_can_be_their_own_neighbor() {
+    // Only tile 0 can be to the right of and below itself, so observing any
+    // other tile has to be reported as a contradiction
+    let rule = contradictory();
+    let (collapsed, counted) = collapse_both(
+        &rule,
+        || Grid::new(1, 1, all_tiles).with_wrap(true, true),
+        |grid| assert_eq!(grid[(0, 0)], state(&[0])),
+    );
+    assert!(collapsed > 0 && counted > 0);
+
+    // Tiles 0 and 1 can be to the right of themselves
+    let (collapsed, counted) = collapse_both(
+        &rule,
+        || Grid::new(1, 6, all_tiles).with_wrap(true, false),
+        |grid| {
+            for y in 0..6 {
+                assert!(state(&[0, 1]).contains(grid[(0, y)].iter().next().unwrap()));
+            }
+        },
+    );
+    assert!(collapsed > 0 && counted > 0);
+}
+
+#[test]
+fn initial_contradictions_are_detected() {
+    let rule = contradictory();
+    let empty_cell = || {
+        Grid::new(4, 4, |x, y| {
+            if (x, y) == (2, 1) {
+                state(&[])
+            } else {
+                all_tiles(x, y)
+            }
+        })
+    };
+    // Tile 3 can't be to the right of itself
+    let conflicting_cells = || {
+        Grid::new(4, 4, |x, y| {
+            if y == 0 && x < 2 {
+                state(&[3])
+            } else {
+                all_tiles(x, y)
+            }
+        })
+    };
+
+    for grid in [empty_cell, conflicting_cells] {
+        let mut rng = StdRng::seed_from_u64(0);
+        let collapsed = collapse(&mut grid(), &rule, &mut rng);
+        let counted = collapse_with_support_counting(&mut grid(), &rule, &mut rng);
+        for result in [collapsed, counted] {
+            assert!(matches!(
+                result,
+                Err(CollapseError::Contradiction {
+                    last_observed: None,
+                    ..
+                })
+            ));
+        }
+    }
+}